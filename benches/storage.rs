@@ -0,0 +1,139 @@
+// storage.rs
+// Compares the sparse-set ComponentStorage against the HashMap<Entity, C>
+// layout it replaced. Run natively with `cargo bench --target <host triple>`.
+use std::collections::HashMap;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lumina_engine::ecs_core::component::ComponentStorage;
+use lumina_engine::ecs_core::entity::{Entity, EntityManager};
+
+#[derive(Clone, Copy)]
+struct Position([f32; 3]);
+
+#[derive(Clone, Copy)]
+struct Velocity([f32; 3]);
+
+const SIZES: [usize; 3] = [1_000, 10_000, 50_000];
+
+fn entities(count: usize) -> Vec<Entity> {
+    let mut manager = EntityManager::new();
+    (0..count).map(|_| manager.create_entity()).collect()
+}
+
+fn sparse_set(entities: &[Entity]) -> (ComponentStorage<Position>, ComponentStorage<Velocity>) {
+    let mut positions = ComponentStorage::new();
+    let mut velocities = ComponentStorage::new();
+    for (i, &entity) in entities.iter().enumerate() {
+        positions.insert(entity, Position([i as f32; 3]));
+        if i % 2 == 0 {
+            velocities.insert(entity, Velocity([1.0; 3]));
+        }
+    }
+    (positions, velocities)
+}
+
+fn hash_map(entities: &[Entity]) -> (HashMap<Entity, Position>, HashMap<Entity, Velocity>) {
+    let mut positions = HashMap::new();
+    let mut velocities = HashMap::new();
+    for (i, &entity) in entities.iter().enumerate() {
+        positions.insert(entity, Position([i as f32; 3]));
+        if i % 2 == 0 {
+            velocities.insert(entity, Velocity([1.0; 3]));
+        }
+    }
+    (positions, velocities)
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for size in SIZES {
+        let entities = entities(size);
+        group.bench_with_input(BenchmarkId::new("sparse_set", size), &entities, |b, entities| {
+            b.iter(|| black_box(sparse_set(entities)))
+        });
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &entities, |b, entities| {
+            b.iter(|| black_box(hash_map(entities)))
+        });
+    }
+    group.finish();
+}
+
+fn iterate(c: &mut Criterion) {
+    let mut group = c.benchmark_group("iterate");
+    for size in SIZES {
+        let entities = entities(size);
+        let (positions, _) = sparse_set(&entities);
+        group.bench_function(BenchmarkId::new("sparse_set", size), |b| {
+            b.iter(|| positions.components().iter().map(|p| p.0[0]).sum::<f32>())
+        });
+        let (positions, _) = hash_map(&entities);
+        group.bench_function(BenchmarkId::new("hash_map", size), |b| {
+            b.iter(|| positions.values().map(|p| p.0[0]).sum::<f32>())
+        });
+    }
+    group.finish();
+}
+
+/// Walks velocities and looks up the matching position, the access pattern a
+/// two-component query has.
+fn join(c: &mut Criterion) {
+    let mut group = c.benchmark_group("join");
+    for size in SIZES {
+        let entities = entities(size);
+        let (mut positions, velocities) = sparse_set(&entities);
+        group.bench_function(BenchmarkId::new("sparse_set", size), |b| {
+            b.iter(|| {
+                for (entity, velocity) in velocities.iter() {
+                    if let Some(position) = positions.get_mut(&entity) {
+                        position.0[0] += velocity.0[0];
+                    }
+                }
+            })
+        });
+        let (mut positions, velocities) = hash_map(&entities);
+        group.bench_function(BenchmarkId::new("hash_map", size), |b| {
+            b.iter(|| {
+                for (entity, velocity) in velocities.iter() {
+                    if let Some(position) = positions.get_mut(entity) {
+                        position.0[0] += velocity.0[0];
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove");
+    for size in SIZES {
+        let entities = entities(size);
+        group.bench_with_input(BenchmarkId::new("sparse_set", size), &entities, |b, entities| {
+            b.iter_batched(
+                || sparse_set(entities).0,
+                |mut positions| {
+                    for entity in entities.iter().step_by(3) {
+                        positions.remove(entity);
+                    }
+                    positions
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(BenchmarkId::new("hash_map", size), &entities, |b, entities| {
+            b.iter_batched(
+                || hash_map(entities).0,
+                |mut positions| {
+                    for entity in entities.iter().step_by(3) {
+                        positions.remove(entity);
+                    }
+                    positions
+                },
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert, iterate, join, remove);
+criterion_main!(benches);
@@ -0,0 +1,15 @@
+#!/bin/sh
+# Builds the browser package into ./pkg with shared-memory threads enabled.
+# Needs a nightly toolchain with rust-src and the wasm32-unknown-unknown target,
+# and wasm-bindgen-cli. Serve the page with
+#   Cross-Origin-Opener-Policy: same-origin
+#   Cross-Origin-Embedder-Policy: require-corp
+# or the browser refuses the shared memory and systems run on the main thread.
+# Shared memory needs atomics in every crate, std included, hence build-std;
+# plain `cargo build --target wasm32-unknown-unknown` makes a single-threaded
+# build that runs systems on the calling thread.
+set -e
+RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals" \
+cargo +nightly build --release --target wasm32-unknown-unknown -Z build-std=panic_abort,std
+wasm-bindgen --target web --out-dir pkg --out-name luminaengine \
+    target/wasm32-unknown-unknown/release/lumina_engine.wasm
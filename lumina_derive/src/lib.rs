@@ -0,0 +1,60 @@
+// lib.rs
+// Derive macros for lumina_engine, re-exported next to the traits they
+// implement.
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Index, Member};
+
+/// Implements `lumina_engine::ecs_core::bundle::Bundle` for a struct whose
+/// fields are components. A field marked `#[bundle]` is a bundle itself and
+/// contributes its components instead of being stored as one.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    bundle(&input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn bundle(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "Bundle can only be derived for structs"));
+    };
+    let bundle = quote!(::lumina_engine::ecs_core::bundle::Bundle);
+    let component_manager = quote!(::lumina_engine::ecs_core::component::ComponentManager);
+
+    let mut component_types = Vec::new();
+    let mut reserve = Vec::new();
+    let mut insert = Vec::new();
+    for (index, field) in data.fields.iter().enumerate() {
+        let ty = &field.ty;
+        let member = field.ident.clone().map_or(Member::Unnamed(Index::from(index)), Member::Named);
+        if field.attrs.iter().any(|attr| attr.path().is_ident("bundle")) {
+            component_types.push(quote!(<#ty as #bundle>::component_types(types);));
+            reserve.push(quote!(<#ty as #bundle>::reserve(components, additional);));
+            insert.push(quote!(#bundle::insert(self.#member, entity, components);));
+        } else {
+            component_types.push(quote!(types.push((::std::any::TypeId::of::<#ty>(), ::std::any::type_name::<#ty>()));));
+            reserve.push(quote!(components.reserve::<#ty>(additional);));
+            insert.push(quote!(components.insert(entity, self.#member);));
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        #[allow(unused_variables)]
+        impl #impl_generics #bundle for #name #ty_generics #where_clause {
+            fn component_types(types: &mut ::std::vec::Vec<(::std::any::TypeId, &'static str)>) {
+                #(#component_types)*
+            }
+
+            fn reserve(components: &mut #component_manager, additional: usize) {
+                #(#reserve)*
+            }
+
+            fn insert(self, entity: ::lumina_engine::ecs_core::entity::Entity, components: &mut #component_manager) {
+                #(#insert)*
+            }
+        }
+    })
+}
@@ -12,4 +12,10 @@ impl InputComponent {
             
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for InputComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,2 +1,3 @@
 pub mod input_component;
-pub mod renderable_component;
\ No newline at end of file
+pub mod renderable_component;
+pub mod transform_component;
\ No newline at end of file
@@ -1,8 +1,16 @@
-pub struct RenderableComponenet {
-}
+// renderable_component.rs
+use crate::engine_core::mesh::MeshHandle;
 
-impl RenderableComponenet {
-    pub fn new() -> Self {
+/// Draws a mesh from `Meshes` at the entity's `GlobalTransform`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenderableComponent {
+    pub mesh: MeshHandle,
+    /// Hidden entities keep their mesh uploaded but are skipped when drawing.
+    pub visible: bool,
+}
 
+impl RenderableComponent {
+    pub fn new(mesh: MeshHandle) -> Self {
+        Self { mesh, visible: true }
     }
-}
\ No newline at end of file
+}
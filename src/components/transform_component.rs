@@ -0,0 +1,110 @@
+// transform_component.rs
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Position, rotation and scale relative to the parent entity, or to the world
+/// for entities without a `Parent`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self { translation, ..Self::IDENTITY }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self { rotation, ..Self::IDENTITY }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self { scale, ..Self::IDENTITY }
+    }
+
+    /// Decomposes an affine matrix. Shear, which a parent with non-uniform scale
+    /// can introduce, is lost.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self { translation, rotation, scale }
+    }
+
+    pub fn with_translation(mut self, translation: Vec3) -> Self {
+        self.translation = translation;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec3) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn compute_matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    /// Blends towards `other`; `t` of 0 is `self`, 1 is `other`.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// World-space matrix of an entity. Written by `TransformPropagationSystem`
+/// every frame from the entity's `Transform` and those of its ancestors; do not
+/// set it by hand. For interpolated entities this is the blended pose meant for
+/// rendering, not the simulated one; use `World::global_matrix` for that.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GlobalTransform(pub Mat4);
+
+impl GlobalTransform {
+    pub const IDENTITY: Self = Self(Mat4::IDENTITY);
+
+    pub fn matrix(&self) -> Mat4 {
+        self.0
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        self.0.w_axis.truncate()
+    }
+
+    pub fn to_transform(self) -> Transform {
+        Transform::from_matrix(self.0)
+    }
+}
+
+impl Default for GlobalTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+/// Opt-in render interpolation for entities moved in `FixedUpdate`. Holds the
+/// `Transform` as of the start of the latest fixed step, and propagation blends
+/// from it to the current one by `FixedTime::interpolation`, so motion looks
+/// smooth at frame rates above the simulation rate. To teleport without
+/// blending, set this to the new `Transform` as well.
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct PreviousTransform(pub Transform);
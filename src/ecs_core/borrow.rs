@@ -0,0 +1,237 @@
+// borrow.rs
+// Runtime-checked borrows out of the storages and resources. `BorrowLock` is a
+// reader-writer flag that never blocks: a conflicting borrow panics instead. It
+// stands in for std's `RwLock` because a web worker that traps mid-system never
+// runs its guards' destructors, and its borrows must still be released (see
+// `release_thread_borrows`).
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicIsize, Ordering};
+use crate::ecs_core::component::AnyStorage;
+
+/// `BorrowLock::state` while it is borrowed mutably.
+const WRITING: isize = -1;
+
+pub(crate) struct BorrowLock<G: ?Sized> {
+    /// Number of shared borrows, or `WRITING`.
+    state: AtomicIsize,
+    value: UnsafeCell<Box<G>>,
+}
+
+// Same bounds as `RwLock`: the value is handed to other threads by `&` and `&mut`.
+unsafe impl<G: ?Sized + Send> Send for BorrowLock<G> {}
+unsafe impl<G: ?Sized + Send + Sync> Sync for BorrowLock<G> {}
+
+impl<G: ?Sized> BorrowLock<G> {
+    pub(crate) fn new(value: Box<G>) -> Self {
+        Self { state: AtomicIsize::new(0), value: UnsafeCell::new(value) }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut G {
+        self.value.get_mut()
+    }
+
+    pub(crate) fn into_inner(self) -> Box<G> {
+        self.value.into_inner()
+    }
+
+    fn try_read(&self) -> Option<ReadGuard<'_, G>> {
+        let mut readers = self.state.load(Ordering::Relaxed);
+        loop {
+            if readers == WRITING {
+                return None;
+            }
+            match self.state.compare_exchange_weak(readers, readers + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => break,
+                Err(current) => readers = current,
+            }
+        }
+        held::track(&self.state, false);
+        Some(ReadGuard { lock: self })
+    }
+
+    fn try_write(&self) -> Option<WriteGuard<'_, G>> {
+        self.state.compare_exchange(0, WRITING, Ordering::Acquire, Ordering::Relaxed).ok()?;
+        held::track(&self.state, true);
+        Some(WriteGuard { lock: self })
+    }
+}
+
+pub(crate) struct ReadGuard<'a, G: ?Sized> {
+    lock: &'a BorrowLock<G>,
+}
+
+pub(crate) struct WriteGuard<'a, G: ?Sized> {
+    lock: &'a BorrowLock<G>,
+}
+
+// The flag grants shared access to readers and exclusive access to the one writer.
+impl<G: ?Sized> Deref for ReadGuard<'_, G> {
+    type Target = G;
+
+    fn deref(&self) -> &G {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<G: ?Sized> Deref for WriteGuard<'_, G> {
+    type Target = G;
+
+    fn deref(&self) -> &G {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<G: ?Sized> DerefMut for WriteGuard<'_, G> {
+    fn deref_mut(&mut self) -> &mut G {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<G: ?Sized> Drop for ReadGuard<'_, G> {
+    fn drop(&mut self) {
+        held::untrack(&self.lock.state, false);
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<G: ?Sized> Drop for WriteGuard<'_, G> {
+    fn drop(&mut self) {
+        held::untrack(&self.lock.state, true);
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+/// Borrows held by the current thread, so a web worker whose job trapped can
+/// give them back. Only tracked where systems run on workers.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+mod held {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicIsize, Ordering};
+
+    thread_local! {
+        /// The flag of each borrow and whether it is the writer.
+        static HELD: RefCell<Vec<(*const AtomicIsize, bool)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    pub(super) fn track(state: &AtomicIsize, write: bool) {
+        HELD.with_borrow_mut(|held| held.push((state, write)));
+    }
+
+    pub(super) fn untrack(state: &AtomicIsize, write: bool) {
+        HELD.with_borrow_mut(|held| {
+            let entry = (state as *const AtomicIsize, write);
+            if let Some(i) = held.iter().rposition(|held| *held == entry) {
+                held.swap_remove(i);
+            }
+        });
+    }
+
+    /// # Safety
+    /// Every lock borrowed on this thread must still be alive, and the guards of
+    /// those borrows must never be dropped.
+    pub(crate) unsafe fn release_all() {
+        for (state, write) in HELD.with_borrow_mut(std::mem::take) {
+            let state = unsafe { &*state };
+            if write {
+                state.store(0, Ordering::Release);
+            } else {
+                state.fetch_sub(1, Ordering::Release);
+            }
+        }
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "atomics")))]
+mod held {
+    use std::sync::atomic::AtomicIsize;
+
+    pub(super) fn track(_state: &AtomicIsize, _write: bool) {}
+
+    pub(super) fn untrack(_state: &AtomicIsize, _write: bool) {}
+}
+
+/// Releases every borrow the current thread still holds. For a web worker whose
+/// job trapped: the trap skipped the guards' destructors.
+///
+/// # Safety
+/// The locks must outlive this call, and the skipped guards must never run.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+pub(crate) unsafe fn release_thread_borrows() {
+    unsafe { held::release_all() }
+}
+
+pub struct Ref<'a, T: ?Sized, G: ?Sized = dyn AnyStorage> {
+    _guard: ReadGuard<'a, G>,
+    value: *const T,
+}
+
+pub struct RefMut<'a, T: ?Sized, G: ?Sized = dyn AnyStorage> {
+    _guard: WriteGuard<'a, G>,
+    value: *mut T,
+}
+
+/// Takes a shared borrow, panicking with `what` if it is currently mutably borrowed.
+pub(crate) fn read<'a, G: ?Sized>(lock: &'a BorrowLock<G>, what: &str) -> ReadGuard<'a, G> {
+    match lock.try_read() {
+        Some(guard) => guard,
+        None => panic!("{} is already borrowed mutably", what),
+    }
+}
+
+/// Takes an exclusive borrow, panicking with `what` if it is currently borrowed at all.
+pub(crate) fn write<'a, G: ?Sized>(lock: &'a BorrowLock<G>, what: &str) -> WriteGuard<'a, G> {
+    match lock.try_write() {
+        Some(guard) => guard,
+        None => panic!("{} is already borrowed", what),
+    }
+}
+
+impl<'a, T: ?Sized, G: ?Sized> Ref<'a, T, G> {
+    /// Narrows the guard to a value inside the locked box, or drops it if `f` finds nothing.
+    pub(crate) fn from_guard(guard: ReadGuard<'a, G>, f: impl FnOnce(&G) -> Option<&T>) -> Option<Self> {
+        let value = f(&*guard)? as *const T;
+        Some(Self { _guard: guard, value })
+    }
+
+    pub fn filter_map<U: ?Sized>(orig: Self, f: impl FnOnce(&T) -> Option<&U>) -> Option<Ref<'a, U, G>> {
+        let value = f(unsafe { &*orig.value })? as *const U;
+        Some(Ref { _guard: orig._guard, value })
+    }
+}
+
+impl<'a, T: ?Sized, G: ?Sized> RefMut<'a, T, G> {
+    pub(crate) fn from_guard(mut guard: WriteGuard<'a, G>, f: impl FnOnce(&mut G) -> Option<&mut T>) -> Option<Self> {
+        let value = f(&mut *guard)? as *mut T;
+        Some(Self { _guard: guard, value })
+    }
+
+    pub fn filter_map<U: ?Sized>(orig: Self, f: impl FnOnce(&mut T) -> Option<&mut U>) -> Option<RefMut<'a, U, G>> {
+        let value = f(unsafe { &mut *orig.value })? as *mut U;
+        Some(RefMut { _guard: orig._guard, value })
+    }
+}
+
+// The pointers were derived from the boxed value the guard keeps borrowed; the
+// box cannot move or be freed while the guard is alive.
+impl<T: ?Sized, G: ?Sized> Deref for Ref<'_, T, G> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<T: ?Sized, G: ?Sized> Deref for RefMut<'_, T, G> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.value }
+    }
+}
+
+impl<T: ?Sized, G: ?Sized> DerefMut for RefMut<'_, T, G> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.value }
+    }
+}
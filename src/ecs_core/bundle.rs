@@ -0,0 +1,167 @@
+// bundle.rs
+use std::any::TypeId;
+use crate::ecs_core::component::{Component, ComponentManager};
+use crate::ecs_core::entity::Entity;
+
+pub use lumina_derive::Bundle;
+
+/// A set of components inserted together, e.g. by `World::spawn`. Implemented
+/// for tuples of up to 12 components; structs get it with `#[derive(Bundle)]`,
+/// where a field marked `#[bundle]` nests another bundle:
+///
+/// ```
+/// use lumina_engine::ecs_core::bundle::Bundle;
+/// use lumina_engine::engine_core::world::World;
+///
+/// struct Position(f32, f32);
+/// struct Velocity(f32, f32);
+/// struct Player;
+///
+/// #[derive(Bundle)]
+/// struct Body {
+///     position: Position,
+///     velocity: Velocity,
+/// }
+///
+/// #[derive(Bundle)]
+/// struct PlayerBundle {
+///     #[bundle]
+///     body: Body,
+///     player: Player,
+/// }
+///
+/// let mut world = World::new();
+/// let player = world.spawn(PlayerBundle {
+///     body: Body { position: Position(0.0, 0.0), velocity: Velocity(1.0, 0.0) },
+///     player: Player,
+/// });
+/// assert_eq!(world.components.get::<Velocity>(&player).unwrap().0, 1.0);
+/// assert!(world.components.get::<Body>(&player).is_none());
+/// ```
+///
+/// Tuples do not nest: since every `Send + Sync + 'static` type is a
+/// `Component`, a tuple inside a tuple is stored as one component. A bundle may
+/// hold each component type only once; spawning one that repeats a type panics.
+pub trait Bundle: Send + Sync + 'static {
+    /// Appends the id and name of every component in the bundle.
+    fn component_types(types: &mut Vec<(TypeId, &'static str)>);
+
+    /// Makes room for `additional` more of each component in the bundle.
+    fn reserve(components: &mut ComponentManager, additional: usize);
+
+    fn insert(self, entity: Entity, components: &mut ComponentManager);
+}
+
+/// Panics if `B` holds a component type more than once, which would otherwise
+/// keep only the last value.
+pub(crate) fn assert_unique<B: Bundle>() {
+    let mut types = Vec::new();
+    B::component_types(&mut types);
+    for (i, (id, name)) in types.iter().enumerate() {
+        if types[..i].iter().any(|(earlier, _)| earlier == id) {
+            panic!("bundle {} contains {} more than once", std::any::type_name::<B>(), name);
+        }
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case, unused_variables)]
+        impl<$($name: Component),*> Bundle for ($($name,)*) {
+            fn component_types(types: &mut Vec<(TypeId, &'static str)>) {
+                $(types.push((TypeId::of::<$name>(), std::any::type_name::<$name>()));)*
+            }
+
+            fn reserve(components: &mut ComponentManager, additional: usize) {
+                $(components.reserve::<$name>(additional);)*
+            }
+
+            fn insert(self, entity: Entity, components: &mut ComponentManager) {
+                let ($($name,)*) = self;
+                $(components.insert(entity, $name);)*
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!();
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_core::world::World;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[derive(Bundle)]
+    struct Body {
+        position: Position,
+        velocity: Velocity,
+    }
+
+    #[derive(Bundle)]
+    struct Named(Name, #[bundle] Body);
+
+    #[derive(Bundle)]
+    struct Repeats {
+        name: Name,
+        #[bundle]
+        inner: (Position, Name),
+    }
+
+    #[test]
+    fn nested_bundles_insert_their_components() {
+        let mut world = World::new();
+        let entity = world.spawn(Named(Name("mover"), Body { position: Position(1), velocity: Velocity(2) }));
+
+        assert_eq!(world.components.get::<Name>(&entity).as_deref(), Some(&Name("mover")));
+        assert_eq!(world.components.get::<Position>(&entity).as_deref(), Some(&Position(1)));
+        assert_eq!(world.components.get::<Velocity>(&entity).as_deref(), Some(&Velocity(2)));
+        assert!(world.components.get::<Body>(&entity).is_none());
+    }
+
+    #[test]
+    fn batches_spawn_every_bundle() {
+        let mut world = World::new();
+        let spawned = world.spawn_batch((0..3).map(|i| Body { position: Position(i), velocity: Velocity(-i) }));
+        assert_eq!(spawned.len(), 3);
+        for (i, entity) in spawned.iter().enumerate() {
+            assert_eq!(world.components.get::<Position>(entity).as_deref(), Some(&Position(i as i32)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "contains")]
+    fn repeated_components_are_rejected() {
+        World::new().spawn((Position(1), Position(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Name more than once")]
+    fn repeats_across_nested_bundles_are_rejected() {
+        World::new().spawn_batch([Repeats { name: Name("a"), inner: (Position(0), Name("b")) }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Velocity more than once")]
+    fn commands_reject_repeats_when_recorded() {
+        World::new().commands().spawn((Velocity(1), Velocity(2)));
+    }
+}
@@ -0,0 +1,209 @@
+// change.rs
+// Change ticks. Every system run takes a fresh tick from its world's counter;
+// a component records the tick it was added at and the tick it was last
+// written at, and a system sees it as added/changed when that tick is newer
+// than the tick of its own previous run.
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// How far the counter may advance before the schedule clamps stored ticks.
+pub const CHECK_TICK_THRESHOLD: u32 = 518_400_000;
+
+/// The oldest a tick is allowed to get. Clamping every `CHECK_TICK_THRESHOLD`
+/// ticks keeps all stored ticks within `u32::MAX` of the counter, so none of
+/// them wraps around and looks newer than it is.
+pub const MAX_CHANGE_AGE: u32 = u32::MAX - (2 * CHECK_TICK_THRESHOLD - 1);
+
+thread_local! {
+    /// Counter, `last_run` and `this_run` of the system running on this thread,
+    /// if any. The counter tells which world the ticks belong to.
+    static SYSTEM_TICKS: Cell<Option<(*const AtomicU32, u32, u32)>> = const { Cell::new(None) };
+}
+
+/// The change counter of one world, so ticks from different worlds never mix.
+/// Clones share the count; a `ComponentManager` and each of its storages hold
+/// one.
+#[derive(Clone, Debug)]
+pub struct ChangeTick {
+    next: Arc<AtomicU32>,
+}
+
+impl ChangeTick {
+    pub fn new() -> Self {
+        Self { next: Arc::new(AtomicU32::new(1)) }
+    }
+
+    /// `(last_run, this_run)` if a system of this world runs on this thread.
+    fn running(&self) -> Option<(u32, u32)> {
+        SYSTEM_TICKS
+            .with(|ticks| ticks.get())
+            .filter(|&(counter, _, _)| std::ptr::eq(counter, &*self.next))
+            .map(|(_, last_run, this_run)| (last_run, this_run))
+    }
+
+    /// The tick writes are stamped with: the running system's tick, or the next
+    /// one to be handed out when called outside a system of this world.
+    pub fn current(&self) -> u32 {
+        self.running().map_or_else(|| self.next.load(Ordering::Acquire), |(_, this_run)| this_run)
+    }
+
+    /// The tick the running system last ran at. Outside a system of this
+    /// world this is 0, so everything counts as added and changed.
+    pub fn last_run(&self) -> u32 {
+        self.running().map_or(0, |(last_run, _)| last_run)
+    }
+
+    /// Runs `f` as a system whose previous run was at `last_run`, and moves
+    /// `last_run` up to the tick this run was given. The counter moves past
+    /// that tick, so writes made after the run are newer than it.
+    pub(crate) fn run_system<R>(&self, last_run: &mut u32, f: impl FnOnce() -> R) -> R {
+        let this_run = self.next.fetch_add(1, Ordering::AcqRel);
+        let outer = SYSTEM_TICKS.with(|ticks| ticks.replace(Some((Arc::as_ptr(&self.next), *last_run, this_run))));
+        let result = f();
+        SYSTEM_TICKS.with(|ticks| ticks.set(outer));
+        *last_run = this_run;
+        result
+    }
+}
+
+impl Default for ChangeTick {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    pub(crate) fn new(tick: u32) -> Self {
+        Self { added: tick, changed: tick }
+    }
+
+    pub(crate) fn clamp(&mut self, now: u32) {
+        clamp_tick(&mut self.added, now);
+        clamp_tick(&mut self.changed, now);
+    }
+
+    pub fn is_added(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.added, last_run, this_run)
+    }
+
+    pub fn is_changed(&self, last_run: u32, this_run: u32) -> bool {
+        is_newer(self.changed, last_run, this_run)
+    }
+}
+
+/// Whether `tick` happened after `last_run`, as seen from `this_run`. Compares
+/// ages rather than raw values so the counter may wrap; ages are capped at
+/// `MAX_CHANGE_AGE`, which is how old a clamped tick claims to be.
+pub fn is_newer(tick: u32, last_run: u32, this_run: u32) -> bool {
+    let tick_age = this_run.wrapping_sub(tick).min(MAX_CHANGE_AGE);
+    let last_run_age = this_run.wrapping_sub(last_run).min(MAX_CHANGE_AGE);
+    tick_age < last_run_age
+}
+
+/// Moves `tick` up to `MAX_CHANGE_AGE` behind `now` if it is older than that.
+pub(crate) fn clamp_tick(tick: &mut u32, now: u32) {
+    if now.wrapping_sub(*tick) > MAX_CHANGE_AGE {
+        *tick = now.wrapping_sub(MAX_CHANGE_AGE);
+    }
+}
+
+/// Mutable access to a component from a query. Only writing through it
+/// (`DerefMut`) marks the component as changed.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(value: &'a mut T, ticks: &'a mut ComponentTicks, last_run: u32, this_run: u32) -> Self {
+        Self { value, ticks, last_run, this_run }
+    }
+
+    pub fn is_added(&self) -> bool {
+        self.ticks.is_added(self.last_run, self.this_run)
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.ticks.is_changed(self.last_run, self.this_run)
+    }
+
+    /// Mutable access that does not mark the component as changed.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+
+    pub fn into_inner(self) -> &'a mut T {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ticks.changed = self.this_run;
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_ticks_do_not_look_new_after_the_counter_wraps() {
+        let (tick, last_run) = (5, 10);
+        // Nearly a full lap later, raw wrapping ages would put `tick` ahead.
+        let now = 4;
+        assert!(!is_newer(tick, last_run, now));
+    }
+
+    #[test]
+    fn clamping_keeps_order_of_recent_ticks() {
+        let (mut old, mut last_run, mut recent) = (1, 2, 3);
+        let mut now: u32 = 3;
+        for _ in 0..8 {
+            now = now.wrapping_add(CHECK_TICK_THRESHOLD);
+            for tick in [&mut old, &mut last_run, &mut recent] {
+                clamp_tick(tick, now);
+            }
+        }
+        assert_eq!(now.wrapping_sub(old), MAX_CHANGE_AGE);
+        assert!(!is_newer(old, last_run, now));
+        assert!(!is_newer(recent, last_run, now));
+
+        let written = now.wrapping_sub(1);
+        assert!(is_newer(written, last_run, now));
+    }
+
+    #[test]
+    fn worlds_count_ticks_independently() {
+        let (ours, theirs) = (ChangeTick::new(), ChangeTick::new());
+        let mut last_run = 0;
+        ours.run_system(&mut last_run, || {});
+        ours.run_system(&mut last_run, || {
+            assert_eq!((ours.last_run(), ours.current()), (1, 2));
+            // Not one of its systems, so it stamps with its own counter.
+            assert_eq!((theirs.last_run(), theirs.current()), (0, 1));
+            assert_eq!(ours.clone().current(), 2);
+        });
+        assert_eq!((ours.current(), theirs.current()), (3, 1));
+    }
+}
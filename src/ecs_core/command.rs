@@ -0,0 +1,210 @@
+// command.rs
+// Deferred structural changes. Systems record spawns, despawns, inserts and
+// removals into a `Commands` buffer while they iterate storages; the schedule
+// applies them at the end of the stage, in system order, so the result does not
+// depend on which parallel system finished first.
+use std::cell::RefCell;
+use std::sync::Mutex;
+use crate::ecs_core::bundle::{self, Bundle};
+use crate::ecs_core::component::Component;
+use crate::ecs_core::entity::{Entity, EntityManager};
+use crate::engine_core::world::World;
+
+type Command = Box<dyn FnOnce(&mut World) + Send>;
+
+thread_local! {
+    /// Queue of the system running on this thread, if any.
+    static RECORDING: RefCell<Option<CommandQueue>> = const { RefCell::new(None) };
+}
+
+/// Commands waiting to be applied to a world, in the order they were recorded.
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, command: impl FnOnce(&mut World) + Send + 'static) {
+        self.commands.push(Box::new(command));
+    }
+
+    pub fn append(&mut self, other: &mut CommandQueue) {
+        self.commands.append(&mut other.commands);
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Makes reserved entities alive, then runs every command in order.
+    pub fn apply(self, world: &mut World) {
+        world.entities.flush();
+        for command in self.commands {
+            command(world);
+        }
+    }
+}
+
+/// Runs `f` as a system, collecting the commands it records into a queue of
+/// its own instead of the world's.
+pub(crate) fn record<R>(f: impl FnOnce() -> R) -> (R, CommandQueue) {
+    let outer = RECORDING.with(|queue| queue.replace(Some(CommandQueue::new())));
+    let result = f();
+    let recorded = RECORDING.with(|queue| queue.replace(outer)).unwrap_or_default();
+    (result, recorded)
+}
+
+/// Records commands for later. Dropping it hands them to the running system's
+/// queue, or to the world's own queue when used outside a system; either way
+/// they are applied at the end of the current stage (or by
+/// `World::apply_commands`).
+pub struct Commands<'w> {
+    entities: &'w EntityManager,
+    world_queue: &'w Mutex<CommandQueue>,
+    queue: CommandQueue,
+}
+
+impl<'w> Commands<'w> {
+    pub(crate) fn new(entities: &'w EntityManager, world_queue: &'w Mutex<CommandQueue>) -> Self {
+        Self { entities, world_queue, queue: CommandQueue::new() }
+    }
+
+    /// Reserves an entity and returns its handle right away. It becomes alive,
+    /// with no components, when the commands are applied.
+    pub fn spawn_empty(&mut self) -> Entity {
+        self.entities.reserve_entity()
+    }
+
+    /// Like `spawn_empty`, with the components of `bundle`.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        bundle::assert_unique::<B>();
+        let entity = self.entities.reserve_entity();
+        self.queue.push(move |world| bundle.insert(entity, &mut world.components));
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.queue.push(move |world| {
+            world.despawn(entity);
+        });
+    }
+
+    /// Skipped if the entity is gone by the time commands are applied.
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) {
+        self.queue.push(move |world| {
+            world.insert(entity, component);
+        });
+    }
+
+    pub fn remove<C: Component>(&mut self, entity: Entity) {
+        self.queue.push(move |world| world.components.remove::<C>(&entity));
+    }
+
+    /// Records an arbitrary change.
+    pub fn add(&mut self, command: impl FnOnce(&mut World) + Send + 'static) {
+        self.queue.push(command);
+    }
+}
+
+impl Drop for Commands<'_> {
+    fn drop(&mut self) {
+        if self.queue.is_empty() {
+            return;
+        }
+        let queue = &mut self.queue;
+        let recorded = RECORDING.with(|recording| match recording.borrow_mut().as_mut() {
+            Some(system_queue) => {
+                system_queue.append(queue);
+                true
+            }
+            None => false,
+        });
+        if !recorded {
+            self.world_queue.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).append(queue);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use super::*;
+    use crate::ecs_core::schedule::{Schedule, Stage, SystemConfig};
+    use crate::ecs_core::system::System;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    struct Marker;
+
+    #[test]
+    fn commands_apply_in_recorded_order() {
+        let mut world = World::new();
+        let kept = world.spawn((Health(1),));
+        let doomed = world.spawn((Health(2),));
+
+        let mut commands = world.commands();
+        let spawned = commands.spawn((Health(3),));
+        commands.insert(kept, Marker);
+        commands.remove::<Marker>(kept);
+        commands.remove::<Health>(kept);
+        commands.insert(kept, Health(10));
+        commands.despawn(doomed);
+        commands.insert(doomed, Health(20));
+        drop(commands);
+
+        assert!(!world.entities.is_alive(spawned));
+        assert!(world.components.get::<Health>(&doomed).is_some());
+
+        world.apply_commands([]);
+        assert_eq!(world.components.get::<Health>(&spawned).as_deref(), Some(&Health(3)));
+        assert_eq!(world.components.get::<Health>(&kept).as_deref(), Some(&Health(10)));
+        assert!(world.components.get::<Marker>(&kept).is_none());
+        assert!(!world.entities.is_alive(doomed));
+        assert!(world.components.get::<Health>(&doomed).is_none());
+    }
+
+    /// Spawns a `Marker` entity through commands on every run.
+    struct Spawner;
+
+    impl System for Spawner {
+        fn update(&mut self, world: &mut World) {
+            world.commands().spawn((Marker,));
+        }
+    }
+
+    /// Logs how many `Marker` entities it can see.
+    struct Count(&'static str, Arc<Mutex<Vec<usize>>>);
+
+    impl System for Count {
+        fn update(&mut self, world: &mut World) {
+            self.1.lock().unwrap().push(world.query::<&Marker>().iter().count());
+        }
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    #[test]
+    fn system_commands_apply_at_the_end_of_the_stage() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, SystemConfig::new(Spawner).label("spawn"))
+            .add_system(Stage::Update, SystemConfig::new(Count("same stage", seen.clone())).after("spawn"))
+            .add_system(Stage::PostUpdate, Count("next stage", seen.clone()));
+        let mut world = World::new();
+
+        schedule.run(&mut world).unwrap();
+        schedule.run(&mut world).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [0, 1, 1, 2]);
+    }
+}
@@ -1,51 +1,149 @@
 // component.rs
 use std::collections::HashMap;
 use std::any::{Any, TypeId};
+use crate::ecs_core::borrow::{self, BorrowLock, Ref, RefMut};
+use crate::ecs_core::change::{self, ChangeTick, ComponentTicks};
 use crate::ecs_core::entity::Entity;
+use crate::ecs_core::query::{Query, QueryData, QueryFilter};
 
-pub trait Component: Any + Sized {}
-impl<T: Any + Sized> Component for T {}
+/// Components must be `Send + Sync` so parallel systems can share the world.
+/// Browser handles and other thread-bound data belong in non-send resources.
+pub trait Component: Any + Send + Sync + Sized {}
+impl<T: Any + Send + Sync + Sized> Component for T {}
 
 pub trait ComponentManagerTrait {
     fn register_component<C: Component>(&mut self);
     fn insert_component<C: Component>(&mut self, entity: Entity, component: C);
     fn remove_component<C: Component>(&mut self, entity: &Entity);
-    fn get_component<C: Component>(&self, entity: &Entity) -> Option<&C>;
+    fn get_component<C: Component>(&self, entity: &Entity) -> Option<Ref<'_, C>>;
     fn get_component_mut<C: Component>(&mut self, entity: &Entity) -> Option<&mut C>;
 }
 
+/// Type-erased view of a `ComponentStorage<C>`, so the manager can act on an
+/// entity across every registered component type without knowing `C`.
+pub trait AnyStorage: Any + Send + Sync {
+    fn remove_entity(&mut self, entity: &Entity) -> bool;
+    fn contains_entity(&self, entity: &Entity) -> bool;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn entities(&self) -> Box<dyn Iterator<Item = Entity> + '_>;
+    /// Forgets removals recorded at or before `tick`.
+    fn clear_removed_before(&mut self, tick: u32);
+    /// Clamps every stored tick; see `change::MAX_CHANGE_AGE`.
+    fn check_change_ticks(&mut self, now: u32);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<C: Component> AnyStorage for ComponentStorage<C> {
+    fn remove_entity(&mut self, entity: &Entity) -> bool {
+        self.remove(entity).is_some()
+    }
+
+    fn contains_entity(&self, entity: &Entity) -> bool {
+        self.contains(entity)
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn entities(&self) -> Box<dyn Iterator<Item = Entity> + '_> {
+        Box::new(self.dense.iter().copied())
+    }
+
+    fn clear_removed_before(&mut self, tick: u32) {
+        let now = self.change_tick.current();
+        self.removed.retain(|&(_, removed_at)| change::is_newer(removed_at, tick, now));
+    }
+
+    fn check_change_ticks(&mut self, now: u32) {
+        for ticks in &mut self.ticks {
+            ticks.clamp(now);
+        }
+        for (_, removed_at) in &mut self.removed {
+            change::clamp_tick(removed_at, now);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Each storage sits behind its own lock so several can be borrowed at once
+/// through `&self` (see `query`). Conflicting borrows panic instead of blocking.
 pub struct ComponentManager {
-    storages: HashMap<TypeId, Box<dyn Any>>,
+    storages: HashMap<TypeId, BorrowLock<dyn AnyStorage>>,
+    /// Tick of the last `clear_trackers`; removals older than the one before
+    /// are dropped so each is visible for two frames.
+    last_clear: u32,
+    /// This world's change counter, shared with every storage.
+    change_tick: ChangeTick,
 }
 
 impl ComponentManager {
     pub fn new() -> Self {
-        Self { storages: HashMap::new(), }
+        Self { storages: HashMap::new(), last_clear: 0, change_tick: ChangeTick::new() }
+    }
+
+    /// The counter component ticks are stamped from.
+    pub fn change_tick(&self) -> &ChangeTick {
+        &self.change_tick
     }
 
     /// Registers a new component type by inserting an empty storage for it.
     pub fn register_component<C: Component>(&mut self) {
         let type_id = TypeId::of::<C>();
-        self.storages.entry(type_id).or_insert_with(|| Box::new(ComponentStorage::<C>::new()));
+        self.storages.entry(type_id).or_insert_with(|| BorrowLock::new(Box::new(ComponentStorage::<C>::with_change_tick(self.change_tick.clone()))));
+    }
+
+    /// Borrows the component storage for type `C`.
+    /// Panics if it is currently borrowed mutably.
+    pub fn storage<C: Component>(&self) -> Option<Ref<'_, ComponentStorage<C>>> {
+        let lock = self.storages.get(&TypeId::of::<C>())?;
+        Ref::from_guard(borrow::read(lock, std::any::type_name::<C>()), |boxed_storage| {
+            boxed_storage.as_any().downcast_ref::<ComponentStorage<C>>()
+        })
     }
 
-     /// Retrieves an immutable reference to the component storage for type `C`.
-    pub fn storage<C: Component>(&self) -> Option<&ComponentStorage<C>> {
-        self.storages.get(&TypeId::of::<C>())
-            .and_then(|boxed_storage| boxed_storage.downcast_ref::<ComponentStorage<C>>())
+    /// Mutably borrows the component storage for type `C` through a shared reference.
+    /// Panics if it is currently borrowed at all.
+    pub fn storage_borrow_mut<C: Component>(&self) -> Option<RefMut<'_, ComponentStorage<C>>> {
+        let lock = self.storages.get(&TypeId::of::<C>())?;
+        RefMut::from_guard(borrow::write(lock, std::any::type_name::<C>()), |boxed_storage| {
+            boxed_storage.as_any_mut().downcast_mut::<ComponentStorage<C>>()
+        })
     }
 
     /// Retrieves a mutable reference to the component storage for type `C`.
     pub fn storage_mut<C: Component>(&mut self) -> Option<&mut ComponentStorage<C>> {
         self.storages.get_mut(&TypeId::of::<C>())
-            .and_then(|boxed_storage| boxed_storage.downcast_mut::<ComponentStorage<C>>())
+            .and_then(|lock| lock.get_mut()
+                .as_any_mut()
+                .downcast_mut::<ComponentStorage<C>>())
+    }
+
+    /// Inserts a component `C` for the given `Entity`. Returns `false` if the
+    /// handle is older than the entity now using its slot; see
+    /// `ComponentStorage::insert`. Use `World::insert` to also reject handles of
+    /// despawned entities.
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) -> bool {
+        self.register_component::<C>();
+        self.storage_mut::<C>().is_some_and(|storage| storage.insert(entity, component))
     }
 
-    /// Inserts a component `C` for the given `Entity`.
-    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) {
+    /// Registers `C` if needed and makes room for `additional` more of it.
+    pub fn reserve<C: Component>(&mut self, additional: usize) {
         self.register_component::<C>();
         if let Some(storage) = self.storage_mut::<C>() {
-            storage.insert(entity, component);
+            storage.reserve(additional);
         }
     }
 
@@ -56,15 +154,67 @@ impl ComponentManager {
         }
     }
 
-    /// Gets an immutable reference to component `C` of the given `Entity`.
-    pub fn get<C: Component>(&self, entity: &Entity) -> Option<&C> {
-        self.storage::<C>()?.get(entity)
+    /// Removes every component of the given `Entity`, across all registered types.
+    pub fn remove_all(&mut self, entity: &Entity) {
+        for lock in self.storages.values_mut() {
+            lock.get_mut().remove_entity(entity);
+        }
+    }
+
+    /// Borrows component `C` of the given `Entity`.
+    pub fn get<C: Component>(&self, entity: &Entity) -> Option<Ref<'_, C>> {
+        Ref::filter_map(self.storage::<C>()?, |storage| storage.get(entity))
     }
 
     /// Gets a mutable reference to component `C` of the given `Entity`.
     pub fn get_mut<C: Component>(&mut self, entity: &Entity) -> Option<&mut C> {
         self.storage_mut::<C>()?.get_mut(entity)
     }
+
+    /// Entities that lost component `C` since the running system last ran.
+    pub fn removed<C: Component>(&self) -> RemovedComponents<'_, C> {
+        RemovedComponents {
+            storage: self.storage::<C>(),
+            last_run: self.change_tick.last_run(),
+            this_run: self.change_tick.current(),
+        }
+    }
+
+    /// Drops removal records older than the previous frame. Called by the
+    /// schedule at the end of every frame.
+    pub fn clear_trackers(&mut self) {
+        let cutoff = self.last_clear;
+        for lock in self.storages.values_mut() {
+            lock.get_mut().clear_removed_before(cutoff);
+        }
+        self.last_clear = self.change_tick.current();
+    }
+
+    /// Clamps the ticks of every component and removal so they cannot wrap
+    /// around. The schedule calls this every `change::CHECK_TICK_THRESHOLD` ticks.
+    pub fn check_change_ticks(&mut self, now: u32) {
+        for lock in self.storages.values_mut() {
+            lock.get_mut().check_change_ticks(now);
+        }
+        change::clamp_tick(&mut self.last_clear, now);
+    }
+
+    /// Iterates entities that have every component in `Q`, e.g.
+    /// `query::<(&Transform, &mut Velocity)>()`.
+    pub fn query<Q: QueryData>(&self) -> Query<'_, Q> {
+        Query::new(self)
+    }
+
+    /// Like `query`, narrowed further by a filter such as `With<T>` or `Without<T>`.
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&self) -> Query<'_, Q, F> {
+        Query::new(self)
+    }
+}
+
+impl Default for ComponentManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ComponentManagerTrait for ComponentManager {
@@ -73,14 +223,14 @@ impl ComponentManagerTrait for ComponentManager {
     }
 
     fn insert_component<C: Component>(&mut self, entity: Entity, component: C) {
-        self.insert(entity, component);
+        self.insert::<C>(entity, component);
     }
 
     fn remove_component<C: Component>(&mut self, entity: &Entity) {
         self.remove::<C>(entity);
     }
 
-    fn get_component<C: Component>(&self, entity: &Entity) -> Option<&C> {
+    fn get_component<C: Component>(&self, entity: &Entity) -> Option<Ref<'_, C>> {
         self.get::<C>(entity)
     }
 
@@ -89,34 +239,247 @@ impl ComponentManagerTrait for ComponentManager {
     }
 }
 
+/// Sparse-set storage: components live in a dense, contiguous array alongside
+/// the entity that owns each one, and `sparse` maps an entity's slot index to its
+/// position in that array. Insert, remove and lookup are O(1) and iteration walks
+/// the dense arrays directly.
+///
+/// Lookups compare the stored handle's generation, so a stale handle never
+/// resolves to the data of whichever entity reused its slot.
+///
+/// Each component also carries the ticks it was added and last changed at,
+/// and removals are logged with their tick, for change detection. Mutable
+/// access through the storage itself counts as a change; queries hand out
+/// `Mut` instead, which only counts actual writes.
 pub struct ComponentStorage<C> {
-    components: HashMap<Entity, C>,
+    sparse: Vec<u32>,
+    dense: Vec<Entity>,
+    data: Vec<C>,
+    ticks: Vec<ComponentTicks>,
+    removed: Vec<(Entity, u32)>,
+    change_tick: ChangeTick,
+}
+
+const EMPTY: u32 = u32::MAX;
+
+/// Generations wrap, so compare by distance like change ticks do.
+fn is_newer_generation(generation: u32, than: u32) -> bool {
+    (generation.wrapping_sub(than) as i32) > 0
 }
 
 impl<C> ComponentStorage<C> {
+    /// A storage with a change counter of its own; one registered with a
+    /// `ComponentManager` shares the manager's instead.
     pub fn new() -> Self {
+        Self::with_change_tick(ChangeTick::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut storage = Self::new();
+        storage.reserve(capacity);
+        storage
+    }
+
+    pub(crate) fn with_change_tick(change_tick: ChangeTick) -> Self {
         Self {
-            components: HashMap::new(),
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            data: Vec::new(),
+            ticks: Vec::new(),
+            removed: Vec::new(),
+            change_tick,
         }
     }
 
-    pub fn insert(&mut self, entity: Entity, component: C) {
-        self.components.insert(entity, component);
+    /// Position of the entity's component in the dense arrays.
+    pub(crate) fn slot(&self, entity: &Entity) -> Option<usize> {
+        let slot = *self.sparse.get(entity.index() as usize)?;
+        if slot != EMPTY && self.dense[slot as usize] == *entity {
+            Some(slot as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Inserts or replaces the component and returns whether it was stored. A
+    /// component left behind in the same slot by an older generation is evicted
+    /// and logged as removed; if the slot belongs to a newer generation the
+    /// insert is ignored, so a stale handle never overwrites the entity that
+    /// reused its index.
+    pub fn insert(&mut self, entity: Entity, component: C) -> bool {
+        let tick = self.change_tick.current();
+        let index = entity.index() as usize;
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, EMPTY);
+        }
+        match self.sparse[index] {
+            EMPTY => {
+                self.sparse[index] = self.dense.len() as u32;
+                self.dense.push(entity);
+                self.data.push(component);
+                self.ticks.push(ComponentTicks::new(tick));
+            }
+            slot => {
+                let slot = slot as usize;
+                let occupant = self.dense[slot];
+                if occupant == entity {
+                    self.ticks[slot].changed = tick;
+                } else if is_newer_generation(entity.generation(), occupant.generation()) {
+                    self.removed.push((occupant, tick));
+                    self.ticks[slot] = ComponentTicks::new(tick);
+                    self.dense[slot] = entity;
+                } else {
+                    return false;
+                }
+                self.data[slot] = component;
+            }
+        }
+        true
+    }
+
+    pub fn remove(&mut self, entity: &Entity) -> Option<C> {
+        let slot = self.slot(entity)?;
+        self.sparse[entity.index() as usize] = EMPTY;
+        self.dense.swap_remove(slot);
+        self.ticks.swap_remove(slot);
+        let component = self.data.swap_remove(slot);
+        self.removed.push((*entity, self.change_tick.current()));
+        if let Some(moved) = self.dense.get(slot) {
+            self.sparse[moved.index() as usize] = slot as u32;
+        }
+        Some(component)
     }
 
-    pub fn remove(&mut self, entity: &Entity) {
-        self.components.remove(entity);
+    pub fn contains(&self, entity: &Entity) -> bool {
+        self.slot(entity).is_some()
     }
 
     pub fn get(&self, entity: &Entity) -> Option<&C> {
-        self.components.get(entity)
+        self.slot(entity).map(|slot| &self.data[slot])
     }
 
     pub fn get_mut(&mut self, entity: &Entity) -> Option<&mut C> {
-        self.components.get_mut(entity)
+        let slot = self.slot(entity)?;
+        self.ticks[slot].changed = self.change_tick.current();
+        Some(&mut self.data[slot])
+    }
+
+    /// Base pointers of the component and tick arrays, indexed by `slot`.
+    /// Valid until the storage is next modified.
+    pub(crate) fn raw_parts_mut(&mut self) -> (*mut C, *mut ComponentTicks) {
+        (self.data.as_mut_ptr(), self.ticks.as_mut_ptr())
+    }
+
+    pub fn ticks(&self, entity: &Entity) -> Option<ComponentTicks> {
+        self.slot(entity).map(|slot| self.ticks[slot])
+    }
+
+    /// Entities whose `C` was removed after `last_run`, as seen from `this_run`.
+    pub fn removed_since(&self, last_run: u32, this_run: u32) -> impl Iterator<Item = Entity> + '_ {
+        self.removed
+            .iter()
+            .filter(move |&&(_, tick)| change::is_newer(tick, last_run, this_run))
+            .map(|&(entity, _)| entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.dense.reserve(additional);
+        self.data.reserve(additional);
+        self.ticks.reserve(additional);
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&Entity, &C)> {
-        self.components.iter()
+    /// Owning entities, parallel to `components`.
+    pub fn entities(&self) -> &[Entity] {
+        &self.dense
+    }
+
+    pub fn components(&self) -> &[C] {
+        &self.data
+    }
+
+    pub fn components_mut(&mut self) -> &mut [C] {
+        let tick = self.change_tick.current();
+        self.ticks.iter_mut().for_each(|ticks| ticks.changed = tick);
+        &mut self.data
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &C)> {
+        self.dense.iter().copied().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut C)> {
+        let tick = self.change_tick.current();
+        self.ticks.iter_mut().for_each(|ticks| ticks.changed = tick);
+        self.dense.iter().copied().zip(self.data.iter_mut())
+    }
+}
+
+impl<C> Default for ComponentStorage<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Entities that lost a `C` since the running system last ran, including
+/// through despawn. Each removal stays visible for two frames.
+pub struct RemovedComponents<'w, C: Component> {
+    storage: Option<Ref<'w, ComponentStorage<C>>>,
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<C: Component> RemovedComponents<'_, C> {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.storage
+            .iter()
+            .flat_map(|storage| storage.removed_since(self.last_run, self.this_run))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs_core::entity::EntityManager;
+
+    #[test]
+    fn stale_handle_cannot_overwrite_reused_slot() {
+        let mut entities = EntityManager::new();
+        let mut storage = ComponentStorage::<u32>::new();
+        let old = entities.create_entity();
+        storage.insert(old, 1);
+        storage.remove(&old);
+        entities.destroy_entity(old);
+        let new = entities.create_entity();
+        assert_eq!(new.index(), old.index());
+
+        assert!(storage.insert(new, 42));
+        assert!(!storage.insert(old, 7));
+        assert_eq!(storage.get(&new), Some(&42));
+        assert_eq!(storage.get(&old), None);
+    }
+
+    #[test]
+    fn newer_generation_evicts_leftover_component() {
+        let mut entities = EntityManager::new();
+        let mut components = ComponentManager::new();
+        let old = entities.create_entity();
+        components.insert(old, 1u32);
+        // Destroyed without stripping its components.
+        entities.destroy_entity(old);
+        let new = entities.create_entity();
+
+        assert!(components.insert(new, 2u32));
+        assert_eq!(components.get::<u32>(&old).as_deref(), None);
+        assert_eq!(components.get::<u32>(&new).as_deref(), Some(&2));
+        let storage = components.storage::<u32>().unwrap();
+        assert_eq!(storage.removed_since(0, components.change_tick().current()).collect::<Vec<_>>(), vec![old]);
     }
 }
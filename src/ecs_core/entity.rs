@@ -1,31 +1,116 @@
 // entity.rs
+use std::sync::atomic::{AtomicI64, Ordering};
 
-pub type Entity = u32;
+/// A handle to an entity. The `index` addresses a slot in the `EntityManager`,
+/// the `generation` is bumped every time that slot is freed so handles held on
+/// to after a destroy can be told apart from whatever reuses the slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
 
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Hands out entity handles and tracks which are alive.
+///
+/// `reserve_entity` only needs `&self`: it moves an atomic cursor down the
+/// recycled list and then past the end of `generations`, and `flush` later turns
+/// everything reserved into live entities. Every `&mut self` method flushes
+/// first.
 pub struct EntityManager {
-    next_entity: Entity,
-    recycled_entities: Vec<Entity>,
+    generations: Vec<u32>,
+    recycled_entities: Vec<u32>,
+    /// Equal to `recycled_entities.len()` when nothing is reserved; each
+    /// reservation decrements it, going negative once the recycled slots run out.
+    free_cursor: AtomicI64,
 }
 
 impl EntityManager {
     pub fn new() -> Self {
         Self {
-            next_entity: 0,
+            generations: Vec::new(),
             recycled_entities: Vec::new(),
+            free_cursor: AtomicI64::new(0),
         }
     }
 
     pub fn create_entity(&mut self) -> Entity {
-        if let Some(entity) = self.recycled_entities.pop() {
-            entity
+        self.flush();
+        let entity = self.allocate();
+        *self.free_cursor.get_mut() = self.recycled_entities.len() as i64;
+        entity
+    }
+
+    /// Reserves a handle for an entity that becomes alive at the next `flush`.
+    /// Safe to call from several threads at once.
+    pub fn reserve_entity(&self) -> Entity {
+        let cursor = self.free_cursor.fetch_sub(1, Ordering::Relaxed);
+        if cursor > 0 {
+            let index = self.recycled_entities[cursor as usize - 1];
+            Entity::new(index, self.generations[index as usize])
         } else {
-            let entity = self.next_entity;
-            self.next_entity += 1;
-            entity
+            Entity::new((self.generations.len() as i64 - cursor) as u32, 0)
         }
     }
 
-    pub fn destroy_entity(&mut self, entity: Entity) {
-        self.recycled_entities.push(entity);
+    /// Makes every reserved entity alive.
+    pub fn flush(&mut self) {
+        let cursor = *self.free_cursor.get_mut();
+        if cursor >= 0 {
+            self.recycled_entities.truncate(cursor as usize);
+        } else {
+            self.recycled_entities.clear();
+            let len = (self.generations.len() as i64 - cursor) as usize;
+            self.generations.resize(len, 0);
+        }
+        *self.free_cursor.get_mut() = self.recycled_entities.len() as i64;
     }
-}
\ No newline at end of file
+
+    fn allocate(&mut self) -> Entity {
+        if let Some(index) = self.recycled_entities.pop() {
+            Entity::new(index, self.generations[index as usize])
+        } else {
+            let index = self.generations.len() as u32;
+            self.generations.push(0);
+            Entity::new(index, 0)
+        }
+    }
+
+    /// Frees the entity's slot and bumps its generation so any copies of the
+    /// handle stop being alive. Returns `false` if the handle was already stale.
+    pub fn destroy_entity(&mut self, entity: Entity) -> bool {
+        self.flush();
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let generation = &mut self.generations[entity.index as usize];
+        *generation = generation.wrapping_add(1);
+        self.recycled_entities.push(entity.index);
+        *self.free_cursor.get_mut() = self.recycled_entities.len() as i64;
+        true
+    }
+
+    /// Returns `true` if the handle refers to an entity that has not been destroyed.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index as usize) == Some(&entity.generation)
+    }
+}
+
+impl Default for EntityManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,216 @@
+// event.rs
+use std::marker::PhantomData;
+use tracing::warn;
+use crate::ecs_core::resource::{ResMut, ResourceManager};
+
+pub trait Event: Send + Sync + 'static {}
+impl<T: Send + Sync + 'static> Event for T {}
+
+struct EventInstance<T> {
+    id: usize,
+    event: T,
+}
+
+/// Double-buffered queue of `T`, stored as a resource. Events sent during a
+/// frame stay readable through the end of the next one; `update` (called by the
+/// schedule after every frame) drops the older buffer and swaps.
+pub struct Events<T: Event> {
+    previous: Vec<EventInstance<T>>,
+    current: Vec<EventInstance<T>>,
+    event_count: usize,
+}
+
+impl<T: Event> Events<T> {
+    pub fn new() -> Self {
+        Self {
+            previous: Vec::new(),
+            current: Vec::new(),
+            event_count: 0,
+        }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.current.push(EventInstance { id: self.event_count, event });
+        self.event_count += 1;
+    }
+
+    /// Swaps the buffers, dropping events sent two frames ago.
+    pub fn update(&mut self) {
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.current.clear();
+    }
+
+    /// A reader that will see every event still buffered.
+    pub fn get_reader(&self) -> EventReader<T> {
+        EventReader::new()
+    }
+
+    /// A reader that only sees events sent from now on.
+    pub fn get_reader_current(&self) -> EventReader<T> {
+        EventReader { last_event_count: self.event_count, _marker: PhantomData }
+    }
+
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn oldest_id(&self) -> usize {
+        self.previous
+            .first()
+            .or(self.current.first())
+            .map_or(self.event_count, |instance| instance.id)
+    }
+}
+
+impl<T: Event> Default for Events<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-reader cursor into an `Events<T>`. Keep one in the system that reads so
+/// each event is seen exactly once by that system.
+pub struct EventReader<T: Event> {
+    last_event_count: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Event> EventReader<T> {
+    pub fn new() -> Self {
+        Self { last_event_count: 0, _marker: PhantomData }
+    }
+
+    /// Returns the events this reader has not seen yet, oldest first, and marks
+    /// them as read.
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> + 'a {
+        let oldest = events.oldest_id();
+        if self.last_event_count < oldest {
+            warn!(
+                "{} missed {} events of {}",
+                std::any::type_name::<Self>(),
+                oldest - self.last_event_count,
+                std::any::type_name::<T>()
+            );
+        }
+        let from = self.last_event_count;
+        self.last_event_count = events.event_count;
+        events
+            .previous
+            .iter()
+            .chain(events.current.iter())
+            .filter(move |instance| instance.id >= from)
+            .map(|instance| &instance.event)
+    }
+
+    /// Number of events `read` would return.
+    pub fn len(&self, events: &Events<T>) -> usize {
+        events.event_count - self.last_event_count.max(events.oldest_id())
+    }
+
+    pub fn is_empty(&self, events: &Events<T>) -> bool {
+        self.len(events) == 0
+    }
+
+    /// Marks everything currently buffered as read.
+    pub fn clear(&mut self, events: &Events<T>) {
+        self.last_event_count = events.event_count;
+    }
+}
+
+impl<T: Event> Default for EventReader<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sends `T` events through a mutable borrow of the `Events<T>` resource.
+pub struct EventWriter<'a, T: Event> {
+    events: ResMut<'a, Events<T>>,
+}
+
+impl<'a, T: Event> EventWriter<'a, T> {
+    pub fn new(events: ResMut<'a, Events<T>>) -> Self {
+        Self { events }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.events.send(event);
+        }
+    }
+}
+
+/// Swaps the buffers of one event type; registered per type by `World::add_event`.
+pub(crate) fn update_events<T: Event>(resources: &ResourceManager) {
+    if let Some(mut events) = resources.get_mut::<Events<T>>() {
+        events.update();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine_core::world::World;
+
+    #[derive(Debug, PartialEq)]
+    struct Hit(u32);
+
+    fn read(reader: &mut EventReader<Hit>, events: &Events<Hit>) -> Vec<u32> {
+        reader.read(events).map(|hit| hit.0).collect()
+    }
+
+    #[test]
+    fn readers_see_each_event_once() {
+        let mut events = Events::new();
+        let mut early = events.get_reader();
+        events.send(Hit(1));
+        let mut late = events.get_reader_current();
+        events.send(Hit(2));
+
+        assert_eq!(early.len(&events), 2);
+        assert_eq!(read(&mut early, &events), [1, 2]);
+        assert!(read(&mut early, &events).is_empty());
+        assert_eq!(read(&mut late, &events), [2]);
+
+        events.update();
+        events.send(Hit(3));
+        assert_eq!(read(&mut early, &events), [3]);
+        assert_eq!(read(&mut late, &events), [3]);
+    }
+
+    #[test]
+    fn events_are_dropped_after_two_updates() {
+        let mut events = Events::new();
+        events.send(Hit(1));
+        events.update();
+        assert_eq!(events.len(), 1);
+        events.update();
+        assert!(events.is_empty());
+
+        // A reader that fell behind skips what was dropped.
+        let mut reader = EventReader::new();
+        events.send(Hit(2));
+        assert_eq!(read(&mut reader, &events), [2]);
+    }
+
+    #[test]
+    fn world_events_live_for_two_frames() {
+        let mut world = World::new();
+        world.add_event::<Hit>();
+        world.send_event(Hit(7));
+        let mut reader = world.events::<Hit>().get_reader();
+
+        world.update_events();
+        assert_eq!(world.events::<Hit>().len(), 1);
+        world.update_events();
+        assert!(read(&mut reader, &world.events::<Hit>()).is_empty());
+    }
+}
@@ -1,4 +1,11 @@
 pub mod component;
 pub mod entity;
 pub mod system;
-pub mod resource;
\ No newline at end of file
+pub mod resource;
+pub mod borrow;
+pub mod query;
+pub mod schedule;
+pub mod event;
+pub mod change;
+pub mod command;
+pub mod bundle;
\ No newline at end of file
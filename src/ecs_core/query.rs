@@ -0,0 +1,511 @@
+// query.rs
+use std::marker::PhantomData;
+use crate::ecs_core::borrow::{Ref, RefMut};
+use crate::ecs_core::change::{ComponentTicks, Mut};
+use crate::ecs_core::component::{AnyStorage, Component, ComponentManager, ComponentStorage};
+use crate::ecs_core::entity::Entity;
+use crate::ecs_core::system::Access;
+use std::any::TypeId;
+
+/// Something that can be fetched per entity by a `Query`: `&C`, `&mut C`
+/// (yielded as `Mut<C>`), `Option<&C>`, `Option<&mut C>` or a tuple of those.
+///
+/// Storages are borrowed when the query is built, so asking for the same
+/// component type mutably twice (or mutably and immutably) panics up front.
+pub trait QueryData {
+    type Fetch<'w>;
+    type Item<'q>;
+
+    /// Borrows the storages this term reads or writes. `None` means a required
+    /// component type has never been registered, so nothing can match.
+    fn fetch(components: &ComponentManager) -> Option<Self::Fetch<'_>>;
+
+    /// Records the component types this term reads and writes.
+    fn add_access(access: &mut Access);
+
+    /// Replaces `driver` with this term's storage if it is required and smaller.
+    fn driver<'a>(fetch: &'a Self::Fetch<'_>, driver: &mut Option<&'a dyn AnyStorage>);
+
+    /// The storage of `type_id` if this term already borrows it, so filters on
+    /// the same component can read it without borrowing it again. The pointer
+    /// is valid for as long as the fetch is.
+    fn storage(fetch: &Self::Fetch<'_>, type_id: TypeId) -> Option<*const dyn AnyStorage>;
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool;
+
+    /// # Safety
+    /// `entity` must satisfy `matches`, and no other item for the same entity
+    /// may be alive while the returned one is.
+    unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: &Entity) -> Self::Item<'q>;
+}
+
+/// Narrows a query without fetching data.
+pub trait QueryFilter {
+    type Fetch<'w>;
+
+    /// `data` is the query's own fetch, created first; see `QueryData::storage`.
+    fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w>;
+    fn add_access(access: &mut Access);
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool;
+}
+
+/// Only match entities that also have `C`.
+pub struct With<C>(PhantomData<C>);
+
+/// Only match entities that do not have `C`.
+pub struct Without<C>(PhantomData<C>);
+
+/// Only match entities whose `C` was added since the system last ran.
+pub struct Added<C>(PhantomData<C>);
+
+/// Only match entities whose `C` was added or written to since the system last
+/// ran. Combines with `&mut C`, e.g. `query_filtered::<&mut C, Changed<C>>()`:
+/// the filter then reads ticks through the query's own borrow.
+pub struct Changed<C>(PhantomData<C>);
+
+/// Fetch state of `&mut C`: the storage borrow plus the ticks handed to each
+/// `Mut` so writes are stamped with the running system's tick.
+///
+/// Items point into the storage's component and tick arrays through base
+/// pointers taken once, while the write guard is fresh. Handing out an item
+/// never reborrows the storage mutably, so it cannot invalidate the shared
+/// borrows iteration holds on the entity array or the items already handed out.
+pub struct FetchMut<'w, C: Component> {
+    storage: RefMut<'w, ComponentStorage<C>>,
+    data: *mut C,
+    ticks: *mut ComponentTicks,
+    last_run: u32,
+    this_run: u32,
+}
+
+/// A storage a filter reads: borrowed by the filter itself, or through the
+/// query's data when that already borrows it (possibly mutably).
+pub enum FilterStorage<'w, C: Component> {
+    Borrowed(Ref<'w, ComponentStorage<C>>),
+    Shared(*const ComponentStorage<C>),
+    Missing,
+}
+
+impl<'w, C: Component> FilterStorage<'w, C> {
+    fn new<Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self {
+        let shared = data.and_then(|data| Q::storage(data, TypeId::of::<C>())).and_then(|storage| {
+            let storage = unsafe { &*storage }.as_any().downcast_ref::<ComponentStorage<C>>()?;
+            Some(storage as *const ComponentStorage<C>)
+        });
+        match shared {
+            Some(storage) => FilterStorage::Shared(storage),
+            None => components.storage::<C>().map_or(FilterStorage::Missing, FilterStorage::Borrowed),
+        }
+    }
+
+    fn get(&self) -> Option<&ComponentStorage<C>> {
+        match self {
+            FilterStorage::Borrowed(storage) => Some(storage),
+            // Items handed out by the query only touch component and tick
+            // elements, never the storage itself, and a filter reads an
+            // entity's ticks before its item exists.
+            FilterStorage::Shared(storage) => Some(unsafe { &**storage }),
+            FilterStorage::Missing => None,
+        }
+    }
+}
+
+/// Fetch state of `Added`/`Changed`.
+pub struct FetchTicks<'w, C: Component> {
+    storage: FilterStorage<'w, C>,
+    last_run: u32,
+    this_run: u32,
+}
+
+impl<'w, C: Component> FetchTicks<'w, C> {
+    fn new<Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self {
+        Self {
+            storage: FilterStorage::new::<Q>(components, data),
+            last_run: components.change_tick().last_run(),
+            this_run: components.change_tick().current(),
+        }
+    }
+}
+
+impl<C: Component> QueryData for &C {
+    type Fetch<'w> = Ref<'w, ComponentStorage<C>>;
+    type Item<'q> = &'q C;
+
+    fn fetch(components: &ComponentManager) -> Option<Self::Fetch<'_>> {
+        components.storage::<C>()
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_read(TypeId::of::<C>());
+    }
+
+    fn driver<'a>(fetch: &'a Self::Fetch<'_>, driver: &mut Option<&'a dyn AnyStorage>) {
+        if driver.is_none_or(|current| fetch.len() < current.len()) {
+            *driver = Some(&**fetch);
+        }
+    }
+
+    fn storage(fetch: &Self::Fetch<'_>, type_id: TypeId) -> Option<*const dyn AnyStorage> {
+        (type_id == TypeId::of::<C>()).then(|| &**fetch as &dyn AnyStorage as *const dyn AnyStorage)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        fetch.contains(entity)
+    }
+
+    unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: &Entity) -> Self::Item<'q> {
+        fetch.get(entity).expect("query item fetched for an entity that does not match")
+    }
+}
+
+impl<C: Component> QueryData for &mut C {
+    type Fetch<'w> = FetchMut<'w, C>;
+    type Item<'q> = Mut<'q, C>;
+
+    fn fetch(components: &ComponentManager) -> Option<Self::Fetch<'_>> {
+        let mut storage = components.storage_borrow_mut::<C>()?;
+        let (data, ticks) = storage.raw_parts_mut();
+        Some(FetchMut {
+            storage,
+            data,
+            ticks,
+            last_run: components.change_tick().last_run(),
+            this_run: components.change_tick().current(),
+        })
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_write(TypeId::of::<C>());
+    }
+
+    fn driver<'a>(fetch: &'a Self::Fetch<'_>, driver: &mut Option<&'a dyn AnyStorage>) {
+        if driver.is_none_or(|current| fetch.storage.len() < current.len()) {
+            *driver = Some(&*fetch.storage);
+        }
+    }
+
+    fn storage(fetch: &Self::Fetch<'_>, type_id: TypeId) -> Option<*const dyn AnyStorage> {
+        (type_id == TypeId::of::<C>()).then(|| &*fetch.storage as &dyn AnyStorage as *const dyn AnyStorage)
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        fetch.storage.contains(entity)
+    }
+
+    unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: &Entity) -> Self::Item<'q> {
+        let slot = fetch.storage.slot(entity).expect("query item fetched for an entity that does not match");
+        // The write guard keeps the arrays from moving, and the caller
+        // guarantees each slot's item is handed out at most once at a time.
+        Mut::new(&mut *fetch.data.add(slot), &mut *fetch.ticks.add(slot), fetch.last_run, fetch.this_run)
+    }
+}
+
+impl<Q: QueryData> QueryData for Option<Q> {
+    type Fetch<'w> = Option<Q::Fetch<'w>>;
+    type Item<'q> = Option<Q::Item<'q>>;
+
+    fn fetch(components: &ComponentManager) -> Option<Self::Fetch<'_>> {
+        Some(Q::fetch(components))
+    }
+
+    fn add_access(access: &mut Access) {
+        Q::add_access(access);
+    }
+
+    fn driver<'a>(_fetch: &'a Self::Fetch<'_>, _driver: &mut Option<&'a dyn AnyStorage>) {}
+
+    fn storage(fetch: &Self::Fetch<'_>, type_id: TypeId) -> Option<*const dyn AnyStorage> {
+        fetch.as_ref().and_then(|inner| Q::storage(inner, type_id))
+    }
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity: &Entity) -> bool {
+        true
+    }
+
+    unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: &Entity) -> Self::Item<'q> {
+        match fetch {
+            Some(inner) if Q::matches(inner, entity) => Some(Q::get(inner, entity)),
+            _ => None,
+        }
+    }
+}
+
+impl QueryFilter for () {
+    type Fetch<'w> = ();
+
+    fn fetch<'w, Q: QueryData>(_components: &'w ComponentManager, _data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {}
+
+    fn add_access(_access: &mut Access) {}
+
+    fn matches(_fetch: &Self::Fetch<'_>, _entity: &Entity) -> bool {
+        true
+    }
+}
+
+impl<C: Component> QueryFilter for With<C> {
+    type Fetch<'w> = FilterStorage<'w, C>;
+
+    fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {
+        FilterStorage::new::<Q>(components, data)
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_read(TypeId::of::<C>());
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        fetch.get().is_some_and(|storage| storage.contains(entity))
+    }
+}
+
+impl<C: Component> QueryFilter for Without<C> {
+    type Fetch<'w> = FilterStorage<'w, C>;
+
+    fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {
+        FilterStorage::new::<Q>(components, data)
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_read(TypeId::of::<C>());
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        !fetch.get().is_some_and(|storage| storage.contains(entity))
+    }
+}
+
+impl<C: Component> QueryFilter for Added<C> {
+    type Fetch<'w> = FetchTicks<'w, C>;
+
+    fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {
+        FetchTicks::new::<Q>(components, data)
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_read(TypeId::of::<C>());
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        fetch
+            .storage
+            .get()
+            .and_then(|storage| storage.ticks(entity))
+            .is_some_and(|ticks| ticks.is_added(fetch.last_run, fetch.this_run))
+    }
+}
+
+impl<C: Component> QueryFilter for Changed<C> {
+    type Fetch<'w> = FetchTicks<'w, C>;
+
+    fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {
+        FetchTicks::new::<Q>(components, data)
+    }
+
+    fn add_access(access: &mut Access) {
+        access.add_component_read(TypeId::of::<C>());
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+        fetch
+            .storage
+            .get()
+            .and_then(|storage| storage.ticks(entity))
+            .is_some_and(|ticks| ticks.is_changed(fetch.last_run, fetch.this_run))
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name:ident),+) => {
+        #[allow(non_snake_case)]
+        impl<$($name: QueryData),+> QueryData for ($($name,)+) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+            type Item<'q> = ($($name::Item<'q>,)+);
+
+            fn fetch(components: &ComponentManager) -> Option<Self::Fetch<'_>> {
+                Some(($($name::fetch(components)?,)+))
+            }
+
+            fn add_access(access: &mut Access) {
+                $($name::add_access(access);)+
+            }
+
+            fn driver<'a>(fetch: &'a Self::Fetch<'_>, driver: &mut Option<&'a dyn AnyStorage>) {
+                let ($($name,)+) = fetch;
+                $($name::driver($name, driver);)+
+            }
+
+            fn storage(fetch: &Self::Fetch<'_>, type_id: TypeId) -> Option<*const dyn AnyStorage> {
+                let ($($name,)+) = fetch;
+                None$(.or_else(|| $name::storage($name, type_id)))+
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::matches($name, entity))&&+
+            }
+
+            unsafe fn get<'q>(fetch: &'q Self::Fetch<'_>, entity: &Entity) -> Self::Item<'q> {
+                let ($($name,)+) = fetch;
+                ($($name::get($name, entity),)+)
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($name: QueryFilter),+> QueryFilter for ($($name,)+) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)+);
+
+            fn fetch<'w, Q: QueryData>(components: &'w ComponentManager, data: Option<&Q::Fetch<'w>>) -> Self::Fetch<'w> {
+                ($($name::fetch::<Q>(components, data),)+)
+            }
+
+            fn add_access(access: &mut Access) {
+                $($name::add_access(access);)+
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: &Entity) -> bool {
+                let ($($name,)+) = fetch;
+                $($name::matches($name, entity))&&+
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+/// Holds the storage borrows for `Q` and `F` until dropped. Iteration walks the
+/// smallest required storage and skips entities missing any other term, so a
+/// query made only of `Option<_>` terms matches nothing.
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+    data: Option<Q::Fetch<'w>>,
+    filter: F::Fetch<'w>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+    pub fn new(components: &'w ComponentManager) -> Self {
+        let data = Q::fetch(components);
+        let filter = F::fetch::<Q>(components, data.as_ref());
+        Self { data, filter }
+    }
+
+    /// Takes `&mut self` so only one iterator (and thus one `&mut` per entity)
+    /// exists at a time.
+    pub fn iter(&mut self) -> impl Iterator<Item = (Entity, Q::Item<'_>)> + use<'_, 'w, Q, F> {
+        let filter = &self.filter;
+        self.data.as_ref().into_iter().flat_map(move |data| {
+            let mut driver = None;
+            Q::driver(data, &mut driver);
+            driver
+                .into_iter()
+                .flat_map(|storage| storage.entities())
+                .filter(move |entity| Q::matches(data, entity) && F::matches(filter, entity))
+                // Every entity comes from one storage, so each is yielded once.
+                .map(move |entity| (entity, unsafe { Q::get(data, &entity) }))
+        })
+    }
+
+    pub fn get(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        let data = self.data.as_ref()?;
+        if Q::matches(data, &entity) && F::matches(&self.filter, &entity) {
+            Some(unsafe { Q::get(data, &entity) })
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs_core::entity::EntityManager;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(i32);
+
+    // Keeps several `Mut` items alive at once while the iterator walks the
+    // entity array, so Miri checks the items never alias each other or it.
+    #[test]
+    fn mutable_items_over_several_entities() {
+        let mut entities = EntityManager::new();
+        let mut components = ComponentManager::new();
+        let spawned: Vec<Entity> = (0..8).map(|_| entities.create_entity()).collect();
+        for (i, &entity) in spawned.iter().enumerate() {
+            components.insert(entity, Position(i as i32));
+            if i % 2 == 0 {
+                components.insert(entity, Velocity(10));
+            }
+        }
+
+        let mut query = components.query::<(&mut Position, Option<&Velocity>)>();
+        let mut items: Vec<_> = query.iter().collect();
+        assert_eq!(items.len(), 8);
+        for (_, (position, velocity)) in items.iter_mut() {
+            position.0 += velocity.map_or(1, |velocity| velocity.0);
+        }
+        drop(items);
+        drop(query);
+
+        for (i, entity) in spawned.iter().enumerate() {
+            let expected = i as i32 + if i % 2 == 0 { 10 } else { 1 };
+            assert_eq!(*components.get::<Position>(entity).unwrap(), Position(expected));
+        }
+    }
+
+    #[test]
+    fn mutable_get_marks_only_written_items_changed() {
+        let mut entities = EntityManager::new();
+        let mut components = ComponentManager::new();
+        let a = entities.create_entity();
+        let b = entities.create_entity();
+        components.insert(a, Position(0));
+        components.insert(b, Position(0));
+        let before = components.storage::<Position>().unwrap().ticks(&b).unwrap();
+
+        // Takes the tick the inserts were stamped with, so the next run's is newer.
+        let mut last_run = 0;
+        components.change_tick().run_system(&mut last_run, || {});
+        components.change_tick().run_system(&mut last_run, || {
+            let mut query = components.query::<&mut Position>();
+            query.get(a).unwrap().0 = 5;
+            let untouched = query.get(b).unwrap();
+            assert_eq!(untouched.0, 0);
+        });
+
+        let storage = components.storage::<Position>().unwrap();
+        assert_eq!(storage.get(&a), Some(&Position(5)));
+        assert_ne!(storage.ticks(&a).unwrap().changed, before.changed);
+        assert_eq!(storage.ticks(&b).unwrap(), before);
+    }
+
+    #[test]
+    fn mutable_query_filtered_on_its_own_component() {
+        let mut entities = EntityManager::new();
+        let mut components = ComponentManager::new();
+        let a = entities.create_entity();
+        let b = entities.create_entity();
+        components.insert(a, Position(0));
+        components.insert(b, Position(0));
+
+        let mut last_run = 0;
+        components.change_tick().run_system(&mut last_run, || {});
+        components.query::<&mut Position>().get(a).unwrap().0 = 1;
+
+        components.change_tick().run_system(&mut last_run, || {
+            let mut query = components.query_filtered::<&mut Position, Changed<Position>>();
+            let changed: Vec<Entity> = query.iter().map(|(entity, _)| entity).collect();
+            assert_eq!(changed, vec![a]);
+            drop(query);
+
+            let mut query = components.query_filtered::<&mut Position, (With<Position>, Added<Position>)>();
+            assert_eq!(query.iter().count(), 0);
+        });
+    }
+}
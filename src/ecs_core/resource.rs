@@ -2,25 +2,194 @@
 use std::any::Any;
 use std::collections::HashMap;
 use std::any::TypeId;
+use std::thread::{self, ThreadId};
+use tracing::warn;
+use crate::ecs_core::borrow::{self, BorrowLock, Ref, RefMut};
 
+pub type Res<'a, T> = Ref<'a, T, dyn Any + Send + Sync>;
+pub type ResMut<'a, T> = RefMut<'a, T, dyn Any + Send + Sync>;
+pub type NonSend<'a, T> = Ref<'a, T, dyn Any>;
+pub type NonSendMut<'a, T> = RefMut<'a, T, dyn Any>;
+
+/// Owns one value per type. Like component storages, each resource sits behind
+/// its own lock, so `get`/`get_mut` only need `&self` and can be held alongside
+/// component queries; conflicting borrows panic.
+///
+/// Values that are not `Send` (JS handles, the canvas, `web_sys::Performance`)
+/// go in the separate non-send map, which may only be touched from the thread
+/// that created the manager.
 pub struct ResourceManager {
-    resources: HashMap<TypeId, &'static dyn Any>,
+    resources: HashMap<TypeId, BorrowLock<dyn Any + Send + Sync>>,
+    non_send: NonSendResources,
+}
+
+struct NonSendResources {
+    owner: ThreadId,
+    resources: HashMap<TypeId, BorrowLock<dyn Any>>,
+}
+
+// Every access goes through `NonSendResources::map`, which panics off the owner
+// thread, and `Drop` leaks rather than dropping elsewhere.
+unsafe impl Send for NonSendResources {}
+unsafe impl Sync for NonSendResources {}
+
+impl NonSendResources {
+    fn map(&self) -> &HashMap<TypeId, BorrowLock<dyn Any>> {
+        self.check_thread();
+        &self.resources
+    }
+
+    fn map_mut(&mut self) -> &mut HashMap<TypeId, BorrowLock<dyn Any>> {
+        self.check_thread();
+        &mut self.resources
+    }
+
+    fn check_thread(&self) {
+        if thread::current().id() != self.owner {
+            panic!("non-send resources can only be accessed from the thread that created the world");
+        }
+    }
+}
+
+impl Drop for NonSendResources {
+    fn drop(&mut self) {
+        if thread::current().id() != self.owner && !self.resources.is_empty() {
+            warn!("leaking {} non-send resources dropped off their owner thread", self.resources.len());
+            std::mem::forget(std::mem::take(&mut self.resources));
+        }
+    }
 }
 
 impl ResourceManager {
     pub fn new() -> Self {
         Self {
             resources: HashMap::new(),
+            non_send: NonSendResources {
+                owner: thread::current().id(),
+                resources: HashMap::new(),
+            },
         }
     }
 
-    pub fn insert<T: 'static>(&mut self, resource: &'static T) {
-        self.resources.insert(TypeId::of::<T>(), resource);
+    /// Inserts a resource, returning the one it replaced.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), BorrowLock::new(Box::new(resource)))
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
     }
 
-    pub fn get<T: 'static>(&self) -> Option<&'static T> {
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
         self.resources
-            .get(&TypeId::of::<T>())
-            .and_then(|&r| r.downcast_ref::<T>())
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Borrows the resource. Panics if it is currently borrowed mutably.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Res<'_, T>> {
+        let lock = self.resources.get(&TypeId::of::<T>())?;
+        Ref::from_guard(borrow::read(lock, std::any::type_name::<T>()), |resource| resource.downcast_ref::<T>())
+    }
+
+    /// Mutably borrows the resource. Panics if it is currently borrowed at all.
+    pub fn get_mut<T: Send + Sync + 'static>(&self) -> Option<ResMut<'_, T>> {
+        let lock = self.resources.get(&TypeId::of::<T>())?;
+        RefMut::from_guard(borrow::write(lock, std::any::type_name::<T>()), |resource| resource.downcast_mut::<T>())
+    }
+
+    pub fn insert_non_send<T: 'static>(&mut self, resource: T) -> Option<T> {
+        self.non_send
+            .map_mut()
+            .insert(TypeId::of::<T>(), BorrowLock::new(Box::new(resource)))
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
     }
-}
\ No newline at end of file
+
+    pub fn remove_non_send<T: 'static>(&mut self) -> Option<T> {
+        self.non_send
+            .map_mut()
+            .remove(&TypeId::of::<T>())
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn contains_non_send<T: 'static>(&self) -> bool {
+        self.non_send.map().contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get_non_send<T: 'static>(&self) -> Option<NonSend<'_, T>> {
+        let lock = self.non_send.map().get(&TypeId::of::<T>())?;
+        Ref::from_guard(borrow::read(lock, std::any::type_name::<T>()), |resource| resource.downcast_ref::<T>())
+    }
+
+    pub fn get_non_send_mut<T: 'static>(&self) -> Option<NonSendMut<'_, T>> {
+        let lock = self.non_send.map().get(&TypeId::of::<T>())?;
+        RefMut::from_guard(borrow::write(lock, std::any::type_name::<T>()), |resource| resource.downcast_mut::<T>())
+    }
+}
+
+impl Default for ResourceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Score(u32);
+
+    #[test]
+    fn resources_are_owned_and_replaced() {
+        let mut resources = ResourceManager::new();
+        assert_eq!(resources.insert(Score(1)), None);
+        assert_eq!(resources.insert(Score(2)), Some(Score(1)));
+
+        resources.get_mut::<Score>().unwrap().0 += 1;
+        assert_eq!(*resources.get::<Score>().unwrap(), Score(3));
+        assert_eq!(resources.remove::<Score>(), Some(Score(3)));
+        assert!(!resources.contains::<Score>());
+        assert!(resources.get::<Score>().is_none());
+    }
+
+    #[test]
+    fn shared_borrows_coexist() {
+        let mut resources = ResourceManager::new();
+        resources.insert(Score(1));
+        let first = resources.get::<Score>().unwrap();
+        let second = resources.get::<Score>().unwrap();
+        assert_eq!(first.0 + second.0, 2);
+        drop((first, second));
+        assert!(resources.get_mut::<Score>().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn conflicting_borrows_panic() {
+        let mut resources = ResourceManager::new();
+        resources.insert(Score(1));
+        let _reading = resources.get::<Score>().unwrap();
+        resources.get_mut::<Score>();
+    }
+
+    #[test]
+    fn non_send_resources_stay_on_their_thread() {
+        let mut resources = ResourceManager::new();
+        resources.insert_non_send(Rc::new(Score(4)));
+        assert_eq!(resources.get_non_send::<Rc<Score>>().unwrap().0, 4);
+        assert!(!resources.contains::<Rc<Score>>());
+
+        let off_thread = std::thread::scope(|scope| {
+            scope.spawn(|| resources.contains_non_send::<Rc<Score>>()).join()
+        });
+        assert!(off_thread.is_err());
+    }
+}
@@ -0,0 +1,653 @@
+// schedule.rs
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use tracing::warn;
+use crate::ecs_core::change;
+use crate::ecs_core::command::{self, CommandQueue};
+use crate::ecs_core::system::{Access, ParallelSystem, System};
+use crate::engine_core::fixed_time::FixedTime;
+#[cfg(target_arch = "wasm32")]
+use crate::engine_core::webworker::WebWorker;
+use crate::engine_core::world::World;
+
+/// Stages run in declaration order once per frame. Ordering constraints only
+/// apply between systems of the same stage.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    PreUpdate,
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    Render,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Render,
+    ];
+}
+
+pub type RunCondition = Box<dyn Fn(&World) -> bool + Send + Sync>;
+
+enum SystemKind {
+    Exclusive(Box<dyn System>),
+    Parallel(Box<dyn ParallelSystem>),
+}
+
+/// A system plus where and when it runs. Built with `SystemConfig::new(system)`
+/// or `SystemConfig::parallel(system)` and the chained `label`/`before`/`after`/
+/// `run_if` calls.
+pub struct SystemConfig {
+    label: String,
+    system: SystemKind,
+    /// `None` for exclusive systems, which conflict with everything.
+    access: Option<Access>,
+    before: Vec<String>,
+    after: Vec<String>,
+    conditions: Vec<RunCondition>,
+    /// Tick of the system's previous run, for change detection.
+    last_run: u32,
+}
+
+impl SystemConfig {
+    pub fn new<S: System + 'static>(system: S) -> Self {
+        Self {
+            label: system.name().to_string(),
+            system: SystemKind::Exclusive(Box::new(system)),
+            access: None,
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            last_run: 0,
+        }
+    }
+
+    pub fn parallel<S: ParallelSystem + 'static>(system: S) -> Self {
+        Self {
+            label: system.name().to_string(),
+            access: Some(system.access()),
+            system: SystemKind::Parallel(Box::new(system)),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+            last_run: 0,
+        }
+    }
+
+    /// Overrides the label other systems use to order against this one.
+    /// Defaults to `System::name`.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.label = label.into();
+        self
+    }
+
+    pub fn before(mut self, label: impl Into<String>) -> Self {
+        self.before.push(label.into());
+        self
+    }
+
+    pub fn after(mut self, label: impl Into<String>) -> Self {
+        self.after.push(label.into());
+        self
+    }
+
+    /// Skips the system for a frame unless every condition returns `true`.
+    pub fn run_if(mut self, condition: impl Fn(&World) -> bool + Send + Sync + 'static) -> Self {
+        self.conditions.push(Box::new(condition));
+        self
+    }
+}
+
+impl<S: System + 'static> From<S> for SystemConfig {
+    fn from(system: S) -> Self {
+        SystemConfig::new(system)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// Two systems share a label.
+    DuplicateLabel { stage: Stage, label: String },
+    /// A `before`/`after` constraint names a label that no system has.
+    UnknownLabel { stage: Stage, system: String, label: String },
+    /// A constraint points at a system in another stage and contradicts the
+    /// stage order, e.g. an `Update` system asking to run before a `PreUpdate` one.
+    CrossStage { system: String, stage: Stage, label: String, other_stage: Stage },
+    /// The constraints within a stage form a cycle; lists the systems that could
+    /// not be ordered (those on the cycle and anything waiting behind it).
+    Cycle { stage: Stage, systems: Vec<String> },
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScheduleError::DuplicateLabel { stage, label } => {
+                write!(f, "label `{}` is used by more than one system in {:?}", label, stage)
+            }
+            ScheduleError::UnknownLabel { stage, system, label } => {
+                write!(f, "`{}` in {:?} is ordered against unknown label `{}`", system, stage, label)
+            }
+            ScheduleError::CrossStage { system, stage, label, other_stage } => write!(
+                f,
+                "`{}` in {:?} cannot be ordered against `{}` in {:?}; stage order already decides it the other way",
+                system, stage, label, other_stage
+            ),
+            ScheduleError::Cycle { stage, systems } => {
+                write!(f, "ordering cycle in {:?} among: {}", stage, systems.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Pair of systems in the same stage with no ordering between them whose data
+/// access conflicts. They run in insertion order, but nothing guarantees that is
+/// what the author meant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ambiguity {
+    pub stage: Stage,
+    pub first: String,
+    pub second: String,
+}
+
+struct StageSystems {
+    systems: Vec<SystemConfig>,
+    /// Indices into `systems`, grouped into batches that can run at the same
+    /// time, in execution order. Valid once built.
+    batches: Vec<Vec<usize>>,
+}
+
+impl StageSystems {
+    fn new() -> Self {
+        Self { systems: Vec::new(), batches: Vec::new() }
+    }
+}
+
+pub struct Schedule {
+    stages: Vec<(Stage, StageSystems)>,
+    ambiguities: Vec<Ambiguity>,
+    dirty: bool,
+    parallel: bool,
+    /// Tick at which stored change ticks were last clamped.
+    last_check_tick: u32,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            stages: Stage::ALL.iter().map(|&stage| (stage, StageSystems::new())).collect(),
+            ambiguities: Vec::new(),
+            dirty: false,
+            parallel: true,
+            last_check_tick: 0,
+        }
+    }
+
+    fn stage_mut(&mut self, stage: Stage) -> &mut StageSystems {
+        &mut self.stages.iter_mut().find(|(s, _)| *s == stage).unwrap().1
+    }
+
+    pub fn add_system(&mut self, stage: Stage, system: impl Into<SystemConfig>) -> &mut Self {
+        self.stage_mut(stage).systems.push(system.into());
+        self.dirty = true;
+        self
+    }
+
+    /// Moves every system of `other` into this schedule.
+    pub fn merge(&mut self, other: Schedule) {
+        for (stage, stage_systems) in other.stages {
+            if !stage_systems.systems.is_empty() {
+                self.stage_mut(stage).systems.extend(stage_systems.systems);
+                self.dirty = true;
+            }
+        }
+    }
+
+    /// With parallel execution off, batches run their systems one after another
+    /// on the calling thread, in the same order the batch lists them.
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.iter().all(|(_, stage)| stage.systems.is_empty())
+    }
+
+    /// Resolves the execution order of every stage. Fails on duplicate or
+    /// unknown labels, orderings that contradict the stage order, and cycles.
+    /// Pairs of unordered systems are logged and kept in `ambiguities`.
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        let mut labels: HashMap<&str, Stage> = HashMap::new();
+        for (stage, stage_systems) in &self.stages {
+            for config in &stage_systems.systems {
+                if labels.insert(&config.label, *stage).is_some() {
+                    return Err(ScheduleError::DuplicateLabel { stage: *stage, label: config.label.clone() });
+                }
+            }
+        }
+
+        // Constraints naming another stage are fine as long as the stage order agrees.
+        for (stage, stage_systems) in &self.stages {
+            for config in &stage_systems.systems {
+                let constraints = config.before.iter().map(|l| (l, true)).chain(config.after.iter().map(|l| (l, false)));
+                for (label, before) in constraints {
+                    let other_stage = *labels.get(label.as_str()).ok_or_else(|| ScheduleError::UnknownLabel {
+                        stage: *stage,
+                        system: config.label.clone(),
+                        label: label.clone(),
+                    })?;
+                    if other_stage != *stage && (other_stage < *stage) == before {
+                        return Err(ScheduleError::CrossStage {
+                            system: config.label.clone(),
+                            stage: *stage,
+                            label: label.clone(),
+                            other_stage,
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut ambiguities = Vec::new();
+        for (stage, stage_systems) in self.stages.iter_mut() {
+            let (batches, stage_ambiguities) = Self::sort_stage(*stage, &stage_systems.systems)?;
+            stage_systems.batches = batches;
+            ambiguities.extend(stage_ambiguities);
+        }
+        for ambiguity in &ambiguities {
+            warn!(
+                "{:?}: `{}` and `{}` access the same data with no ordering between them",
+                ambiguity.stage, ambiguity.first, ambiguity.second
+            );
+        }
+        self.ambiguities = ambiguities;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Kahn's algorithm, taking the lowest insertion index whenever several
+    /// systems are ready so the result is deterministic. The sorted order is then
+    /// cut into batches of parallel systems with compatible access.
+    fn sort_stage(stage: Stage, systems: &[SystemConfig]) -> Result<(Vec<Vec<usize>>, Vec<Ambiguity>), ScheduleError> {
+        let count = systems.len();
+        let index: HashMap<&str, usize> = systems.iter().enumerate().map(|(i, c)| (c.label.as_str(), i)).collect();
+
+        let mut successors = vec![BTreeSet::new(); count];
+        for (i, config) in systems.iter().enumerate() {
+            for label in &config.before {
+                if let Some(&j) = index.get(label.as_str()) {
+                    successors[i].insert(j);
+                }
+            }
+            for label in &config.after {
+                if let Some(&j) = index.get(label.as_str()) {
+                    successors[j].insert(i);
+                }
+            }
+        }
+
+        let mut in_degree = vec![0usize; count];
+        for edges in &successors {
+            for &j in edges {
+                in_degree[j] += 1;
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..count).filter(|&i| in_degree[i] == 0).collect();
+        let mut order = Vec::with_capacity(count);
+        while let Some(i) = ready.pop_first() {
+            order.push(i);
+            for &j in &successors[i] {
+                in_degree[j] -= 1;
+                if in_degree[j] == 0 {
+                    ready.insert(j);
+                }
+            }
+        }
+
+        if order.len() != count {
+            let systems = (0..count)
+                .filter(|&i| in_degree[i] > 0)
+                .map(|i| systems[i].label.clone())
+                .collect();
+            return Err(ScheduleError::Cycle { stage, systems });
+        }
+
+        // Walking the order backwards, each system reaches its successors and
+        // everything they reach.
+        let mut reachable = vec![vec![false; count]; count];
+        for &i in order.iter().rev() {
+            for &j in &successors[i] {
+                let through = reachable[j].clone();
+                reachable[i][j] = true;
+                for (reached, through) in reachable[i].iter_mut().zip(through) {
+                    *reached |= through;
+                }
+            }
+        }
+
+        let compatible = |a: usize, b: usize| match (&systems[a].access, &systems[b].access) {
+            (Some(first), Some(second)) => first.is_compatible(second),
+            _ => false,
+        };
+
+        let mut ambiguities = Vec::new();
+        for a in 0..count {
+            for b in (a + 1)..count {
+                if !reachable[a][b] && !reachable[b][a] && !compatible(a, b) {
+                    ambiguities.push(Ambiguity {
+                        stage,
+                        first: systems[a].label.clone(),
+                        second: systems[b].label.clone(),
+                    });
+                }
+            }
+        }
+
+        // A system joins the open batch only if nothing in it must run first
+        // and its access is compatible with everything already there.
+        let mut batches = Vec::new();
+        let mut current: Vec<usize> = Vec::new();
+        for &i in &order {
+            let joins = current.iter().all(|&j| !reachable[j][i] && compatible(j, i));
+            if !joins && !current.is_empty() {
+                batches.push(std::mem::take(&mut current));
+            }
+            current.push(i);
+        }
+        if !current.is_empty() {
+            batches.push(current);
+        }
+
+        Ok((batches, ambiguities))
+    }
+
+    pub fn ambiguities(&self) -> &[Ambiguity] {
+        &self.ambiguities
+    }
+
+    /// Runs every system of one stage, batch by batch, then applies the
+    /// commands they recorded in execution order. Rebuilds first if systems were
+    /// added since the last build.
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World) -> Result<(), ScheduleError> {
+        if self.dirty {
+            self.build()?;
+        }
+        let parallel = self.parallel;
+        let change_tick = world.components.change_tick().clone();
+        let StageSystems { systems, batches } = self.stage_mut(stage);
+        let mut queues = Vec::new();
+        for batch in batches.iter() {
+            let mut jobs: Vec<(&mut Box<dyn ParallelSystem>, &mut u32)> = Vec::new();
+            for (i, config) in systems.iter_mut().enumerate() {
+                if !batch.contains(&i) || !config.conditions.iter().all(|condition| condition(world)) {
+                    continue;
+                }
+                match &mut config.system {
+                    // Exclusive systems always form a batch of their own.
+                    SystemKind::Exclusive(system) => {
+                        let last_run = &mut config.last_run;
+                        let ((), queue) = command::record(|| change_tick.run_system(last_run, || system.update(world)));
+                        queues.push(queue);
+                    }
+                    SystemKind::Parallel(system) => jobs.push((system, &mut config.last_run)),
+                }
+            }
+            queues.extend(run_batch(world, jobs, parallel));
+        }
+        world.apply_commands(queues);
+        Ok(())
+    }
+
+    /// Runs all stages in order, then swaps event buffers so events live for
+    /// the frame they were sent in and the next one, and likewise ages out
+    /// recorded component removals.
+    ///
+    /// With a `FixedTime` resource, `FixedUpdate` runs once per banked step,
+    /// which may be zero times; without one it runs once per frame.
+    pub fn run(&mut self, world: &mut World) -> Result<(), ScheduleError> {
+        for stage in Stage::ALL {
+            if stage == Stage::FixedUpdate && world.resources.contains::<FixedTime>() {
+                while expend_fixed_step(world) {
+                    self.run_stage(stage, world)?;
+                }
+            } else {
+                self.run_stage(stage, world)?;
+            }
+        }
+        world.update_events();
+        world.components.clear_trackers();
+
+        let now = world.components.change_tick().current();
+        if now.wrapping_sub(self.last_check_tick) >= change::CHECK_TICK_THRESHOLD {
+            self.check_change_ticks(world, now);
+        }
+        Ok(())
+    }
+
+    /// Clamps the previous-run tick of every system, and every component
+    /// tick, so nothing ages far enough to wrap around.
+    pub fn check_change_ticks(&mut self, world: &mut World, now: u32) {
+        for (_, stage_systems) in &mut self.stages {
+            for config in &mut stage_systems.systems {
+                change::clamp_tick(&mut config.last_run, now);
+            }
+        }
+        world.components.check_change_ticks(now);
+        self.last_check_tick = now;
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Takes one banked step off `FixedTime`; `false` once none are left.
+fn expend_fixed_step(world: &World) -> bool {
+    world.resource_mut::<FixedTime>().is_some_and(|mut fixed| fixed.expend())
+}
+
+/// Runs a batch of compatible systems: scoped threads on native targets, the
+/// installed `WebWorker` pool in the browser.
+///
+/// Each system's change ticks and command queue are set up on whichever thread
+/// runs it; the queues come back in the order the systems were passed in.
+fn run_batch(
+    world: &World,
+    systems: Vec<(&mut Box<dyn ParallelSystem>, &mut u32)>,
+    parallel: bool,
+) -> Vec<CommandQueue> {
+    let mut queues: Vec<CommandQueue> = systems.iter().map(|_| CommandQueue::new()).collect();
+    let change_tick = world.components.change_tick();
+    let run = |system: &mut Box<dyn ParallelSystem>, last_run: &mut u32, queue: &mut CommandQueue| {
+        *queue = command::record(|| change_tick.run_system(last_run, || system.run(world))).1;
+    };
+
+    if !parallel || systems.len() < 2 {
+        for ((system, last_run), queue) in systems.into_iter().zip(queues.iter_mut()) {
+            run(system, last_run, queue);
+        }
+        return queues;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    std::thread::scope(|scope| {
+        let mut jobs = systems.into_iter().zip(queues.iter_mut());
+        let ((first, first_last_run), first_queue) = jobs.next().unwrap();
+        for ((system, last_run), queue) in jobs {
+            scope.spawn(move || run(system, last_run, queue));
+        }
+        run(first, first_last_run, first_queue);
+    });
+
+    #[cfg(target_arch = "wasm32")]
+    WebWorker::run_scoped(
+        systems
+            .into_iter()
+            .zip(queues.iter_mut())
+            .map(|((system, last_run), queue)| {
+                Box::new(move || run(system, last_run, queue)) as Box<dyn FnOnce() + Send + '_>
+            })
+            .collect(),
+    );
+
+    queues
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use super::*;
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    /// Appends its name, which is also its label, to a shared log.
+    struct Record(&'static str, Log);
+
+    impl System for Record {
+        fn update(&mut self, _world: &mut World) {
+            self.1.lock().unwrap().push(self.0);
+        }
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    fn record(name: &'static str, log: &Log) -> SystemConfig {
+        SystemConfig::new(Record(name, log.clone()))
+    }
+
+    /// Does nothing with the access it declares.
+    struct Declare(&'static str, Access);
+
+    impl ParallelSystem for Declare {
+        fn access(&self) -> Access {
+            self.1.clone()
+        }
+
+        fn run(&mut self, _world: &World) {}
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    fn declare(name: &'static str, access: Access) -> SystemConfig {
+        SystemConfig::parallel(Declare(name, access))
+    }
+
+    fn batches(schedule: &mut Schedule, stage: Stage) -> Vec<Vec<&'static str>> {
+        schedule.build().unwrap();
+        let stage = schedule.stage_mut(stage);
+        let labels: Vec<&'static str> = stage.systems.iter().map(|config| match &config.system {
+            SystemKind::Exclusive(system) => system.name(),
+            SystemKind::Parallel(system) => system.name(),
+        }).collect();
+        stage.batches.iter().map(|batch| batch.iter().map(|&i| labels[i]).collect()).collect()
+    }
+
+    #[test]
+    fn stages_then_constraints_decide_the_order() {
+        let log = Log::default();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::PostUpdate, record("last", &log))
+            .add_system(Stage::Update, record("third", &log).after("second"))
+            .add_system(Stage::Update, record("second", &log))
+            .add_system(Stage::Update, record("first", &log).before("second"))
+            .add_system(Stage::PreUpdate, record("early", &log));
+
+        schedule.run(&mut World::new()).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["early", "first", "second", "third", "last"]);
+    }
+
+    #[test]
+    fn cycles_fail_the_build() {
+        let log = Log::default();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, record("a", &log).before("b"))
+            .add_system(Stage::Update, record("b", &log).before("c"))
+            .add_system(Stage::Update, record("c", &log).before("a"))
+            .add_system(Stage::Update, record("free", &log));
+
+        let error = schedule.run(&mut World::new()).unwrap_err();
+        assert_eq!(error, ScheduleError::Cycle { stage: Stage::Update, systems: vec!["a".into(), "b".into(), "c".into()] });
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn unordered_conflicting_systems_are_ambiguous() {
+        let log = Log::default();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, record("a", &log))
+            .add_system(Stage::Update, record("b", &log).after("a"))
+            .add_system(Stage::Update, record("c", &log))
+            .add_system(Stage::Render, record("d", &log));
+        schedule.build().unwrap();
+
+        let pairs: Vec<(&str, &str)> =
+            schedule.ambiguities().iter().map(|a| (a.first.as_str(), a.second.as_str())).collect();
+        assert_eq!(pairs, [("a", "c"), ("b", "c")]);
+        assert!(schedule.ambiguities().iter().all(|a| a.stage == Stage::Update));
+    }
+
+    #[test]
+    fn run_conditions_are_checked_every_run() {
+        let log = Log::default();
+        let enabled = Arc::new(AtomicBool::new(false));
+        let condition = enabled.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, record("gated", &log).run_if(move |_| condition.load(Ordering::Relaxed)))
+            .add_system(Stage::Update, record("always", &log));
+        let mut world = World::new();
+
+        schedule.run(&mut world).unwrap();
+        enabled.store(true, Ordering::Relaxed);
+        schedule.run(&mut world).unwrap();
+        assert_eq!(*log.lock().unwrap(), ["always", "gated", "always"]);
+    }
+
+    struct Position;
+    struct Velocity;
+
+    #[test]
+    fn compatible_parallel_systems_share_a_batch() {
+        let log = Log::default();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, declare("move", Access::new().query::<(&mut Position, &Velocity)>()))
+            .add_system(Stage::Update, declare("read velocity", Access::new().read::<Velocity>()))
+            .add_system(Stage::Update, declare("read position", Access::new().read::<Position>()))
+            .add_system(Stage::Update, declare("accelerate", Access::new().write::<Velocity>()))
+            .add_system(Stage::Update, record("exclusive", &log))
+            .add_system(Stage::Update, declare("after", Access::new()));
+
+        assert_eq!(
+            batches(&mut schedule, Stage::Update),
+            [vec!["move", "read velocity"], vec!["read position", "accelerate"], vec!["exclusive"], vec!["after"]],
+        );
+    }
+
+    #[test]
+    fn ordered_systems_never_share_a_batch() {
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, declare("first", Access::new().read::<Position>()))
+            .add_system(Stage::Update, declare("second", Access::new().read::<Position>()).after("first"))
+            .add_system(Stage::Update, declare("free", Access::new().read::<Position>()));
+
+        assert_eq!(batches(&mut schedule, Stage::Update), [vec!["first"], vec!["second", "free"]]);
+    }
+}
@@ -1,6 +1,130 @@
 // system.rs
+use std::any::TypeId;
+use std::collections::HashSet;
+use crate::ecs_core::component::Component;
+use crate::ecs_core::query::{QueryData, QueryFilter};
 use crate::engine_core::world::World;
 
-pub trait System {
+/// A system that needs the whole world. The scheduler runs it on its own,
+/// never alongside another system.
+pub trait System: Send + Sync {
     fn update(&mut self, world: &mut World);
-}
\ No newline at end of file
+
+    /// Used as the default label for ordering and in schedule diagnostics.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// A system that only needs `&World` and declares up front which components
+/// and resources it reads and writes. The scheduler runs parallel systems with
+/// compatible access at the same time. Touching anything outside the declared
+/// set is not undefined behaviour, but it may panic on a borrow conflict.
+pub trait ParallelSystem: Send + Sync {
+    fn access(&self) -> Access;
+
+    fn run(&mut self, world: &World);
+
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+}
+
+/// The read and write sets of a `ParallelSystem`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    component_reads: HashSet<TypeId>,
+    component_writes: HashSet<TypeId>,
+    resource_reads: HashSet<TypeId>,
+    resource_writes: HashSet<TypeId>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<C: Component>(mut self) -> Self {
+        self.component_reads.insert(TypeId::of::<C>());
+        self
+    }
+
+    pub fn write<C: Component>(mut self) -> Self {
+        self.component_writes.insert(TypeId::of::<C>());
+        self
+    }
+
+    pub fn read_resource<R: 'static>(mut self) -> Self {
+        self.resource_reads.insert(TypeId::of::<R>());
+        self
+    }
+
+    pub fn write_resource<R: 'static>(mut self) -> Self {
+        self.resource_writes.insert(TypeId::of::<R>());
+        self
+    }
+
+    /// Adds everything a query over `Q` borrows, e.g.
+    /// `Access::new().query::<(&Transform, &mut Velocity)>()`.
+    pub fn query<Q: QueryData>(mut self) -> Self {
+        Q::add_access(&mut self);
+        self
+    }
+
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(mut self) -> Self {
+        Q::add_access(&mut self);
+        F::add_access(&mut self);
+        self
+    }
+
+    pub(crate) fn add_component_read(&mut self, type_id: TypeId) {
+        self.component_reads.insert(type_id);
+    }
+
+    pub(crate) fn add_component_write(&mut self, type_id: TypeId) {
+        self.component_writes.insert(type_id);
+    }
+
+    /// Two accesses are compatible when neither writes something the other
+    /// reads or writes.
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        fn clash(writes: &HashSet<TypeId>, reads: &HashSet<TypeId>, other_writes: &HashSet<TypeId>) -> bool {
+            writes.iter().any(|id| reads.contains(id) || other_writes.contains(id))
+        }
+        !clash(&self.component_writes, &other.component_reads, &other.component_writes)
+            && !clash(&other.component_writes, &self.component_reads, &self.component_writes)
+            && !clash(&self.resource_writes, &other.resource_reads, &other.resource_writes)
+            && !clash(&other.resource_writes, &self.resource_reads, &self.resource_writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Position;
+    struct Velocity;
+    struct Gravity;
+
+    #[test]
+    fn readers_are_compatible_with_each_other() {
+        let first = Access::new().read::<Position>().read_resource::<Gravity>();
+        let second = Access::new().query::<(&Position, &Velocity)>().read_resource::<Gravity>();
+        assert!(first.is_compatible(&second));
+        assert!(second.is_compatible(&first));
+    }
+
+    #[test]
+    fn a_write_conflicts_with_any_other_use() {
+        let writer = Access::new().write::<Position>();
+        assert!(!writer.is_compatible(&Access::new().read::<Position>()));
+        assert!(!Access::new().query::<&Position>().is_compatible(&writer));
+        assert!(!writer.is_compatible(&writer));
+        assert!(writer.is_compatible(&Access::new().write::<Velocity>()));
+
+        let resource_writer = Access::new().write_resource::<Gravity>();
+        assert!(!resource_writer.is_compatible(&Access::new().read_resource::<Gravity>()));
+        // Components and resources are separate namespaces.
+        assert!(resource_writer.is_compatible(&Access::new().read::<Gravity>()));
+    }
+}
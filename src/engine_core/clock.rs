@@ -0,0 +1,158 @@
+// clock.rs
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Source of the current time for `AdvancedTime`. Only differences between
+/// readings matter; the origin is up to the implementation.
+pub trait Clock {
+    /// Milliseconds since some fixed point, with sub-millisecond precision
+    /// where the platform offers it.
+    fn now_ms(&self) -> f64;
+}
+
+/// `performance.now()` of the browser window.
+#[cfg(target_arch = "wasm32")]
+pub struct PerformanceClock {
+    performance: web_sys::Performance,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl PerformanceClock {
+    /// Panics outside a window context (e.g. in a worker).
+    pub fn new() -> Self {
+        let performance = web_sys::window()
+            .expect("no global accessible window exists")
+            .performance()
+            .expect("window should have performance");
+        Self { performance }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Default for PerformanceClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for PerformanceClock {
+    fn now_ms(&self) -> f64 {
+        self.performance.now()
+    }
+}
+
+/// Monotonic system time, measured from when the clock was created.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct InstantClock {
+    start: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InstantClock {
+    pub fn new() -> Self {
+        Self { start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for InstantClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for InstantClock {
+    fn now_ms(&self) -> f64 {
+        self.start.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a test
+/// can keep one and hand another to `AdvancedTime::with_clock`.
+#[derive(Clone, Default)]
+pub struct MockClock {
+    now_bits: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    pub fn new(start_ms: f64) -> Self {
+        Self { now_bits: Arc::new(AtomicU64::new(start_ms.to_bits())) }
+    }
+
+    pub fn set(&self, now_ms: f64) {
+        self.now_bits.store(now_ms.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn advance(&self, delta_ms: f64) {
+        self.set(self.now_ms() + delta_ms);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_ms(&self) -> f64 {
+        f64::from_bits(self.now_bits.load(Ordering::Relaxed))
+    }
+}
+
+/// The real-time clock of the current platform.
+pub fn platform_clock() -> Box<dyn Clock> {
+    #[cfg(target_arch = "wasm32")]
+    return Box::new(PerformanceClock::new());
+
+    #[cfg(not(target_arch = "wasm32"))]
+    return Box::new(InstantClock::new());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use crate::engine_core::temporal::AdvancedTime;
+
+    /// 16 ms ticks of four 4 ms sub-ticks, on a clock that starts at 1000 ms.
+    fn time() -> (MockClock, AdvancedTime) {
+        let clock = MockClock::new(1000.0);
+        let mut time = AdvancedTime::with_clock(Box::new(clock.clone()), 4, 4);
+        time.update();
+        assert!(time.resynced());
+        (clock, time)
+    }
+
+    #[test]
+    fn delta_follows_the_clock() {
+        let (clock, mut time) = time();
+        clock.advance(16.5);
+        time.update();
+
+        assert!(!time.resynced());
+        assert_eq!(time.delta(), Duration::from_secs_f64(0.0165));
+        assert_eq!(time.get_delta_time(), 16);
+        assert_eq!(time.take_ticks(), 1);
+
+        clock.advance(0.5);
+        time.update();
+        assert_eq!(time.get_delta_time(), 1);
+        assert_eq!(time.elapsed(), Duration::from_millis(17));
+    }
+
+    #[test]
+    fn mock_clock_clones_share_the_time() {
+        let clock = MockClock::new(5.0);
+        let handed_out = clock.clone();
+        clock.advance(2.5);
+        assert_eq!(handed_out.now_ms(), 7.5);
+        handed_out.set(1.0);
+        assert_eq!(clock.now_ms(), 1.0);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn instant_clock_does_not_go_backwards() {
+        let clock = InstantClock::new();
+        let first = clock.now_ms();
+        assert!(first >= 0.0);
+        assert!(clock.now_ms() >= first);
+    }
+}
@@ -1,45 +1,297 @@
 // core_loop.rs
-use crate::systems::input_system::InputSystem;
-use crate::ecs_core::system::System;
-use crate::engine_core::world::World;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
 use crate::LuminaEngine;
 
-use super::temporal::{self, AdvancedTime};
+#[cfg(target_arch = "wasm32")]
+use std::cell::{Cell, RefCell};
+#[cfg(target_arch = "wasm32")]
+use std::rc::Rc;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
 
+const RUNNING: u8 = 0;
+const PAUSED: u8 = 1;
+const STOPPED: u8 = 2;
+
+/// Shared run state of an `EngineLoop`. Clones control the same loop, so one
+/// can be handed to whatever decides when to pause or quit.
+#[derive(Clone)]
+pub struct LoopControl {
+    state: Arc<AtomicU8>,
+}
+
+impl LoopControl {
+    fn new() -> Self {
+        Self { state: Arc::new(AtomicU8::new(RUNNING)) }
+    }
+
+    /// Ends the loop for good; it cannot be resumed.
+    pub fn stop(&self) {
+        self.state.store(STOPPED, Ordering::Release);
+    }
+
+    /// Stops running frames until `resume`. Does nothing once stopped.
+    pub fn pause(&self) {
+        let _ = self.state.compare_exchange(RUNNING, PAUSED, Ordering::AcqRel, Ordering::Acquire);
+    }
+
+    pub fn resume(&self) {
+        let _ = self.state.compare_exchange(PAUSED, RUNNING, Ordering::AcqRel, Ordering::Acquire);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.load(Ordering::Acquire) == PAUSED
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.load(Ordering::Acquire) == STOPPED
+    }
+}
+
+/// Drives `LuminaEngine::update` once per frame. In the browser frames come
+/// from `requestAnimationFrame` (`start`); natively `run` paces them on the
+/// calling thread, and `step` runs a single one for headless use and tests.
 pub struct EngineLoop {
-    core: &'static LuminaEngine,
-    world: World,
+    engine: LuminaEngine,
+    control: LoopControl,
 }
 
 impl EngineLoop {
-    pub fn new(core: &'static LuminaEngine) -> Self {
-        let world = World::new(core);
-        Self { core, world }
+    pub fn new(engine: LuminaEngine) -> Self {
+        Self { engine, control: LoopControl::new() }
+    }
+
+    pub fn control(&self) -> LoopControl {
+        self.control.clone()
+    }
+
+    pub fn engine(&self) -> &LuminaEngine {
+        &self.engine
+    }
+
+    pub fn engine_mut(&mut self) -> &mut LuminaEngine {
+        &mut self.engine
+    }
+
+    pub fn into_engine(self) -> LuminaEngine {
+        self.engine
+    }
+
+    /// Runs one frame now, regardless of the pause state.
+    pub fn step(&mut self) {
+        self.engine.update();
+    }
+
+    /// Runs frames at most every `frame_time` until the loop is stopped, then
+    /// hands the engine back. While paused it only sleeps, and on resume the
+    /// time spent paused is skipped rather than simulated.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run(mut self, frame_time: std::time::Duration) -> LuminaEngine {
+        let mut was_paused = false;
+        while !self.control.is_stopped() {
+            let frame_start = std::time::Instant::now();
+            if self.control.is_paused() {
+                was_paused = true;
+            } else {
+                if was_paused {
+                    self.engine.resync_time();
+                    was_paused = false;
+                }
+                self.engine.update();
+            }
+            if let Some(remaining) = frame_time.checked_sub(frame_start.elapsed()) {
+                std::thread::sleep(remaining);
+            }
+        }
+        self.engine
+    }
+
+    /// Starts running a frame per `requestAnimationFrame` callback, passing its
+    /// timestamp to the time system. The loop lives as long as the returned
+    /// handle, which controls it from JS.
+    #[cfg(target_arch = "wasm32")]
+    pub fn start(self) -> EngineHandle {
+        let control = self.control.clone();
+        let inner = Rc::new(LoopInner {
+            engine_loop: RefCell::new(self),
+            control,
+            resync: Cell::new(false),
+            frame: RefCell::new(FrameRequest { callback: None, request_id: None }),
+        });
+
+        let weak = Rc::downgrade(&inner);
+        let callback = Closure::<dyn FnMut(f64)>::new(move |timestamp: f64| {
+            let Some(inner) = weak.upgrade() else { return };
+            inner.frame.borrow_mut().request_id = None;
+            if !inner.is_running() {
+                return;
+            }
+            // Only the engine is borrowed while the frame runs, so anything it
+            // calls may pause, resume or stop the loop through the handle.
+            {
+                let mut engine_loop = inner.engine_loop.borrow_mut();
+                if inner.resync.take() {
+                    engine_loop.engine.resync_time();
+                }
+                engine_loop.engine.update_at(timestamp);
+            }
+            if inner.is_running() {
+                inner.frame.borrow_mut().request_frame();
+            }
+        });
+        inner.frame.borrow_mut().callback = Some(callback);
+        inner.frame.borrow_mut().request_frame();
+
+        EngineHandle { inner }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct LoopInner {
+    engine_loop: RefCell<EngineLoop>,
+    control: LoopControl,
+    /// Set by `EngineHandle::resume`; the next frame skips the paused time.
+    resync: Cell<bool>,
+    frame: RefCell<FrameRequest>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl LoopInner {
+    fn is_running(&self) -> bool {
+        !self.control.is_stopped() && !self.control.is_paused()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+struct FrameRequest {
+    callback: Option<Closure<dyn FnMut(f64)>>,
+    /// Id of the pending `requestAnimationFrame`, if any.
+    request_id: Option<i32>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl FrameRequest {
+    fn request_frame(&mut self) {
+        if self.request_id.is_some() {
+            return;
+        }
+        let Some(callback) = &self.callback else { return };
+        let window = web_sys::window().expect("no global accessible window exists");
+        match window.request_animation_frame(callback.as_ref().unchecked_ref()) {
+            Ok(id) => self.request_id = Some(id),
+            Err(e) => ::tracing::error!("requestAnimationFrame failed: {:?}", e),
+        }
     }
 
-    pub fn start(self) {
-        // Assuming we're in a WASM environment, we might want to use 
-        // wasm_bindgen_futures::spawn_local to start the async loop
-        wasm_bindgen_futures::spawn_local(self.run_loop());
+    fn cancel_frame(&mut self) {
+        if let Some(id) = self.request_id.take() {
+            if let Some(window) = web_sys::window() {
+                let _ = window.cancel_animation_frame(id);
+            }
+        }
     }
+}
 
-    async fn run_loop(mut self) {
-        loop {
-            // Update game state
-            self.core.temporal.update();
+/// JS-facing handle of a running `EngineLoop`. Never borrows the engine, so
+/// it can be used from inside a frame.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub struct EngineHandle {
+    inner: Rc<LoopInner>,
+}
 
-            // Render frame
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+impl EngineHandle {
+    /// Stops the loop for good.
+    pub fn stop(&self) {
+        self.inner.control.stop();
+        let mut frame = self.inner.frame.borrow_mut();
+        frame.cancel_frame();
+        // Dropping the closure from inside its own call is fine; wasm-bindgen
+        // frees it once the call returns.
+        frame.callback = None;
+    }
 
-            // Yield to browser to keep things responsive
-            // wasm_bindgen_futures::JsFuture::from(js_sys::Promise::new(&mut |resolve, _| {
-            //     web_sys::window()
-            //         .unwrap()
-            //         .request_animation_frame(&resolve)
-            //         .unwrap();
-            // }))
-            // .await
-            // .unwrap();
+    pub fn pause(&self) {
+        self.inner.control.pause();
+        self.inner.frame.borrow_mut().cancel_frame();
+    }
+
+    /// Continues after `pause`. The paused time is skipped, not simulated.
+    pub fn resume(&self) {
+        if !self.inner.control.is_paused() {
+            return;
         }
+        self.inner.control.resume();
+        self.inner.resync.set(true);
+        self.inner.frame.borrow_mut().request_frame();
+    }
+
+    #[wasm_bindgen(js_name = isPaused)]
+    pub fn is_paused(&self) -> bool {
+        self.inner.control.is_paused()
     }
 
-}
\ No newline at end of file
+    #[wasm_bindgen(js_name = isStopped)]
+    pub fn is_stopped(&self) -> bool {
+        self.inner.control.is_stopped()
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+    use crate::ecs_core::schedule::Stage;
+    use crate::ecs_core::system::System;
+    use crate::engine_core::world::World;
+
+    struct Frames(u32);
+
+    /// Counts frames and stops the loop on the third.
+    struct StopAfterThree;
+
+    impl System for StopAfterThree {
+        fn update(&mut self, world: &mut World) {
+            let mut frames = world.resource_mut::<Frames>().unwrap();
+            frames.0 += 1;
+            if frames.0 == 3 {
+                world.resource::<LoopControl>().unwrap().stop();
+            }
+        }
+    }
+
+    fn engine_loop() -> EngineLoop {
+        let mut engine = LuminaEngine::headless();
+        engine.world_mut().insert_resource(Frames(0));
+        engine.world_mut().schedule.add_system(Stage::Update, StopAfterThree);
+        let mut engine_loop = EngineLoop::new(engine);
+        let control = engine_loop.control();
+        engine_loop.engine_mut().world_mut().insert_resource(control);
+        engine_loop
+    }
+
+    fn frames(engine: &LuminaEngine) -> u32 {
+        engine.world().resource::<Frames>().unwrap().0
+    }
+
+    #[test]
+    fn step_runs_one_frame_even_while_paused() {
+        let mut engine_loop = engine_loop();
+        engine_loop.step();
+        engine_loop.control().pause();
+        engine_loop.step();
+        assert_eq!(frames(engine_loop.engine()), 2);
+        assert!(engine_loop.control().is_paused());
+    }
+
+    #[test]
+    fn run_returns_once_a_system_stops_the_loop() {
+        let engine = engine_loop().run(Duration::ZERO);
+        assert_eq!(frames(&engine), 3);
+    }
+}
@@ -0,0 +1,143 @@
+// fixed_time.rs
+use tracing::warn;
+
+/// Drives the `FixedUpdate` stage. Each frame the engine hands it the number of
+/// whole `AdvancedTime` ticks that elapsed, and the schedule runs `FixedUpdate`
+/// once per tick, so the simulation advances in steps of `step_ms` regardless
+/// of frame rate.
+///
+/// After a long stall (a backgrounded tab, a breakpoint) running every missed
+/// step would make the next frame even slower; anything beyond
+/// `max_steps_per_frame` is dropped instead, and the simulation falls behind
+/// wall-clock time.
+#[derive(Clone, Debug)]
+pub struct FixedTime {
+    step_ms: u32,
+    max_steps_per_frame: u32,
+    pending_steps: u32,
+    steps: u64,
+    dropped_steps: u64,
+    interpolation: f32,
+}
+
+impl FixedTime {
+    pub fn new(step_ms: u32) -> Self {
+        Self {
+            step_ms,
+            max_steps_per_frame: 5,
+            pending_steps: 0,
+            steps: 0,
+            dropped_steps: 0,
+            interpolation: 0.0,
+        }
+    }
+
+    pub fn with_max_steps_per_frame(mut self, max_steps_per_frame: u32) -> Self {
+        self.max_steps_per_frame = max_steps_per_frame.max(1);
+        self
+    }
+
+    pub fn step_ms(&self) -> u32 {
+        self.step_ms
+    }
+
+    /// Length of one step in seconds, for integrating inside `FixedUpdate`.
+    pub fn step_seconds(&self) -> f32 {
+        self.step_ms as f32 / 1000.0
+    }
+
+    pub fn max_steps_per_frame(&self) -> u32 {
+        self.max_steps_per_frame
+    }
+
+    /// Banks `ticks` more steps for this frame, and records how far into the
+    /// next tick the clock is (0..1) for interpolation.
+    pub fn accumulate(&mut self, ticks: u64, interpolation: f32) {
+        let total = self.pending_steps as u64 + ticks;
+        let allowed = total.min(self.max_steps_per_frame as u64);
+        if total > allowed {
+            let dropped = total - allowed;
+            self.dropped_steps += dropped;
+            warn!("fixed update fell behind; dropping {} steps", dropped);
+        }
+        self.pending_steps = allowed as u32;
+        self.interpolation = interpolation.clamp(0.0, 1.0);
+    }
+
+    /// Takes one banked step. The schedule runs `FixedUpdate` for as long as
+    /// this returns `true`.
+    pub(crate) fn expend(&mut self) -> bool {
+        if self.pending_steps == 0 {
+            return false;
+        }
+        self.pending_steps -= 1;
+        self.steps += 1;
+        true
+    }
+
+    /// How far the clock is between the last simulated step and the next, from
+    /// 0 to 1. Rendering blends the previous and current step by this much.
+    pub fn interpolation(&self) -> f32 {
+        self.interpolation
+    }
+
+    /// Steps run since the start.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Steps skipped because of `max_steps_per_frame`.
+    pub fn dropped_steps(&self) -> u64 {
+        self.dropped_steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_pending(fixed: &mut FixedTime) -> u32 {
+        let mut steps = 0;
+        while fixed.expend() {
+            steps += 1;
+        }
+        steps
+    }
+
+    #[test]
+    fn one_step_per_tick() {
+        let mut fixed = FixedTime::new(16);
+        fixed.accumulate(2, 0.25);
+        assert_eq!(run_pending(&mut fixed), 2);
+        assert_eq!(fixed.interpolation(), 0.25);
+
+        fixed.accumulate(0, 0.75);
+        assert_eq!(run_pending(&mut fixed), 0);
+        assert_eq!(fixed.steps(), 2);
+        assert_eq!(fixed.step_seconds(), 0.016);
+    }
+
+    #[test]
+    fn catch_up_is_capped_after_a_stall() {
+        let mut fixed = FixedTime::new(16).with_max_steps_per_frame(5);
+        // A one second stall at 16 ms ticks.
+        fixed.accumulate(62, 0.5);
+        assert_eq!(run_pending(&mut fixed), 5);
+        assert_eq!(fixed.dropped_steps(), 57);
+        assert_eq!(fixed.interpolation(), 0.5);
+
+        fixed.accumulate(1, 0.5);
+        assert_eq!(run_pending(&mut fixed), 1);
+        assert_eq!(fixed.steps(), 6);
+    }
+
+    #[test]
+    fn unspent_steps_count_towards_the_cap() {
+        let mut fixed = FixedTime::new(16).with_max_steps_per_frame(3);
+        fixed.accumulate(2, 0.0);
+        fixed.accumulate(2, 2.0);
+        assert_eq!(fixed.dropped_steps(), 1);
+        assert_eq!(fixed.interpolation(), 1.0);
+        assert_eq!(run_pending(&mut fixed), 3);
+    }
+}
@@ -1,9 +1,147 @@
-pub struct InputHandler {
+// inputhandler.rs
+use std::sync::{Arc, Mutex, MutexGuard};
+use serde::{Deserialize, Serialize};
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsCast;
+#[cfg(target_arch = "wasm32")]
+use web_sys::HtmlCanvasElement;
 
+/// A single piece of user input. Browser listeners queue these on the
+/// `InputHandler`; each frame they are handed to systems as `InputEvent`
+/// events, which is also what recordings store.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    /// `KeyboardEvent.code`, e.g. `"KeyW"`.
+    KeyDown { code: String },
+    KeyUp { code: String },
+    /// Canvas-relative position in CSS pixels.
+    MouseMove { x: f32, y: f32 },
+    /// `MouseEvent.button`: 0 is the main button.
+    MouseDown { button: u16 },
+    MouseUp { button: u16 },
+    Wheel { delta_x: f32, delta_y: f32 },
+}
+
+/// Input received since the last frame, waiting to be sent to systems.
+pub struct InputHandler {
+    pending: Arc<Mutex<Vec<InputEvent>>>,
 }
 
 impl InputHandler {
     pub fn new() -> Self {
-        Self { }    
+        Self { pending: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    pub fn push(&mut self, event: InputEvent) {
+        self.sender().send(event);
+    }
+
+    /// A handle that queues input from outside the world, such as from a
+    /// browser event listener.
+    pub fn sender(&self) -> InputSender {
+        InputSender { pending: self.pending.clone() }
+    }
+
+    /// Takes everything queued, oldest first.
+    pub fn drain(&mut self) -> Vec<InputEvent> {
+        std::mem::take(&mut *lock(&self.pending))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        lock(&self.pending).is_empty()
     }
-}
\ No newline at end of file
+}
+
+impl Default for InputHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Queues input on the `InputHandler` it came from. Clones share the queue.
+#[derive(Clone)]
+pub struct InputSender {
+    pending: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl InputSender {
+    pub fn send(&self, event: InputEvent) {
+        lock(&self.pending).push(event);
+    }
+}
+
+fn lock(pending: &Mutex<Vec<InputEvent>>) -> MutexGuard<'_, Vec<InputEvent>> {
+    pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// A registered DOM listener: its target, event type and callback.
+#[cfg(target_arch = "wasm32")]
+type Listener = (web_sys::EventTarget, &'static str, Closure<dyn FnMut(web_sys::Event)>);
+
+/// Keyboard listeners on the window and mouse listeners on the canvas, feeding
+/// an `InputSender`. The listeners are removed when this is dropped.
+#[cfg(target_arch = "wasm32")]
+pub struct BrowserInput {
+    listeners: Vec<Listener>,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl BrowserInput {
+    pub fn attach(canvas: &HtmlCanvasElement, sender: InputSender) -> Self {
+        let window = web_sys::window().expect("no global accessible window exists");
+        let mut input = Self { listeners: Vec::new() };
+
+        input.listen(&window, "keydown", &sender, |event| {
+            let event = event.unchecked_ref::<web_sys::KeyboardEvent>();
+            (!event.repeat()).then(|| InputEvent::KeyDown { code: event.code() })
+        });
+        input.listen(&window, "keyup", &sender, |event| {
+            Some(InputEvent::KeyUp { code: event.unchecked_ref::<web_sys::KeyboardEvent>().code() })
+        });
+        input.listen(canvas, "mousemove", &sender, |event| {
+            let event = event.unchecked_ref::<web_sys::MouseEvent>();
+            Some(InputEvent::MouseMove { x: event.offset_x() as f32, y: event.offset_y() as f32 })
+        });
+        input.listen(canvas, "mousedown", &sender, |event| {
+            Some(InputEvent::MouseDown { button: event.unchecked_ref::<web_sys::MouseEvent>().button() as u16 })
+        });
+        input.listen(canvas, "mouseup", &sender, |event| {
+            Some(InputEvent::MouseUp { button: event.unchecked_ref::<web_sys::MouseEvent>().button() as u16 })
+        });
+        input.listen(canvas, "wheel", &sender, |event| {
+            let event = event.unchecked_ref::<web_sys::WheelEvent>();
+            Some(InputEvent::Wheel { delta_x: event.delta_x() as f32, delta_y: event.delta_y() as f32 })
+        });
+        input
+    }
+
+    fn listen(
+        &mut self,
+        target: &web_sys::EventTarget,
+        kind: &'static str,
+        sender: &InputSender,
+        convert: impl Fn(&web_sys::Event) -> Option<InputEvent> + 'static,
+    ) {
+        let sender = sender.clone();
+        let closure = Closure::<dyn FnMut(web_sys::Event)>::new(move |event: web_sys::Event| {
+            if let Some(input) = convert(&event) {
+                sender.send(input);
+            }
+        });
+        match target.add_event_listener_with_callback(kind, closure.as_ref().unchecked_ref()) {
+            Ok(()) => self.listeners.push((target.clone(), kind, closure)),
+            Err(e) => ::tracing::error!("could not listen for {} events: {:?}", kind, e),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Drop for BrowserInput {
+    fn drop(&mut self) {
+        for (target, kind, closure) in &self.listeners {
+            let _ = target.remove_event_listener_with_callback(kind, closure.as_ref().unchecked_ref());
+        }
+    }
+}
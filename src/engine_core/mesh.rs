@@ -0,0 +1,512 @@
+// mesh.rs
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+/// One mesh vertex, laid out for `Vertex::layout`. Attribute locations:
+/// 0 position, 1 normal, 2 uv, 3 tangent (w is the bitangent sign), 4 color.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub tangent: [f32; 4],
+    pub color: [f32; 4],
+}
+
+// Implemented by hand: the derives' generated layout check trips the
+// dead-code lint. Every field is plain `f32`s with no padding between them.
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x3,
+        2 => Float32x2,
+        3 => Float32x4,
+        4 => Float32x4,
+    ];
+
+    /// A white vertex facing +Z, with uv at the origin.
+    pub fn new(position: [f32; 3]) -> Self {
+        Self {
+            position,
+            normal: [0.0, 0.0, 1.0],
+            uv: [0.0, 0.0],
+            tangent: [1.0, 0.0, 0.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+
+    pub fn with_normal(mut self, normal: [f32; 3]) -> Self {
+        self.normal = normal;
+        self
+    }
+
+    pub fn with_uv(mut self, uv: [f32; 2]) -> Self {
+        self.uv = uv;
+        self
+    }
+
+    pub fn with_tangent(mut self, tangent: [f32; 4]) -> Self {
+        self.tangent = tangent;
+        self
+    }
+
+    pub fn with_color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// For `wgpu::VertexState::buffers` in pipelines drawing meshes.
+    pub fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn format(&self) -> wgpu::IndexFormat {
+        match self {
+            Indices::U16(_) => wgpu::IndexFormat::Uint16,
+            Indices::U32(_) => wgpu::IndexFormat::Uint32,
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => bytemuck::cast_slice(indices),
+            Indices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+/// Geometry on the CPU. Add it to `Meshes` to have it drawn; the GPU copy is
+/// made by `MeshUploadSystem`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mesh {
+    pub topology: wgpu::PrimitiveTopology,
+    pub vertices: Vec<Vertex>,
+    /// `None` draws the vertices in order.
+    pub indices: Option<Indices>,
+}
+
+impl Mesh {
+    /// A non-indexed triangle list.
+    pub fn new(vertices: Vec<Vertex>) -> Self {
+        Self { topology: wgpu::PrimitiveTopology::TriangleList, vertices, indices: None }
+    }
+
+    /// An indexed triangle list.
+    pub fn indexed(vertices: Vec<Vertex>, indices: Indices) -> Self {
+        Self { indices: Some(indices), ..Self::new(vertices) }
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.indices.is_some()
+    }
+
+    /// Indices to draw when indexed, vertices otherwise.
+    pub fn draw_count(&self) -> u32 {
+        match &self.indices {
+            Some(indices) => indices.len() as u32,
+            None => self.vertices.len() as u32,
+        }
+    }
+}
+
+/// Refers to a mesh in `Meshes`. Handles of removed meshes stay invalid even
+/// after their slot is reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshHandle {
+    index: u32,
+    generation: u32,
+}
+
+struct MeshSlot {
+    generation: u32,
+    mesh: Option<Mesh>,
+    /// Bumped whenever the mesh may have changed.
+    version: u64,
+}
+
+/// Every mesh the world can draw.
+pub struct Meshes {
+    slots: Vec<MeshSlot>,
+    free: Vec<u32>,
+}
+
+impl Meshes {
+    pub fn new() -> Self {
+        Self { slots: Vec::new(), free: Vec::new() }
+    }
+
+    pub fn add(&mut self, mesh: Mesh) -> MeshHandle {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.mesh = Some(mesh);
+            slot.version += 1;
+            return MeshHandle { index, generation: slot.generation };
+        }
+        self.slots.push(MeshSlot { generation: 0, mesh: Some(mesh), version: 0 });
+        MeshHandle { index: self.slots.len() as u32 - 1, generation: 0 }
+    }
+
+    pub fn get(&self, handle: MeshHandle) -> Option<&Mesh> {
+        self.slot(handle)?.mesh.as_ref()
+    }
+
+    /// Marks the mesh changed, so it is uploaded again next frame.
+    pub fn get_mut(&mut self, handle: MeshHandle) -> Option<&mut Mesh> {
+        let slot = self.slots.get_mut(handle.index as usize).filter(|slot| slot.generation == handle.generation)?;
+        let mesh = slot.mesh.as_mut()?;
+        slot.version += 1;
+        Some(mesh)
+    }
+
+    /// Its GPU buffers are freed on the next upload.
+    pub fn remove(&mut self, handle: MeshHandle) -> Option<Mesh> {
+        let slot = self.slots.get_mut(handle.index as usize).filter(|slot| slot.generation == handle.generation)?;
+        let mesh = slot.mesh.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        Some(mesh)
+    }
+
+    pub fn contains(&self, handle: MeshHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (MeshHandle, &Mesh)> {
+        self.versions().map(|(handle, mesh, _)| (handle, mesh))
+    }
+
+    fn slot(&self, handle: MeshHandle) -> Option<&MeshSlot> {
+        self.slots.get(handle.index as usize).filter(|slot| slot.generation == handle.generation)
+    }
+
+    fn versions(&self) -> impl Iterator<Item = (MeshHandle, &Mesh, u64)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let handle = MeshHandle { index: index as u32, generation: slot.generation };
+            slot.mesh.as_ref().map(|mesh| (handle, mesh, slot.version))
+        })
+    }
+}
+
+impl Default for Meshes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A mesh's buffers on the GPU.
+pub struct GpuMesh {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: Option<(wgpu::Buffer, wgpu::IndexFormat)>,
+    draw_count: u32,
+    topology: wgpu::PrimitiveTopology,
+}
+
+impl GpuMesh {
+    pub fn vertex_buffer(&self) -> &wgpu::Buffer {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> Option<(&wgpu::Buffer, wgpu::IndexFormat)> {
+        self.index_buffer.as_ref().map(|(buffer, format)| (buffer, *format))
+    }
+
+    pub fn draw_count(&self) -> u32 {
+        self.draw_count
+    }
+
+    /// Pipelines drawing this mesh must use this topology.
+    pub fn topology(&self) -> wgpu::PrimitiveTopology {
+        self.topology
+    }
+
+    /// Binds the buffers (the vertex buffer at `slot`) and draws `instances`.
+    pub fn draw(&self, pass: &mut wgpu::RenderPass<'_>, slot: u32, instances: Range<u32>) {
+        pass.set_vertex_buffer(slot, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some((buffer, format)) => {
+                pass.set_index_buffer(buffer.slice(..), *format);
+                pass.draw_indexed(0..self.draw_count, 0, instances);
+            }
+            None => pass.draw(0..self.draw_count, instances),
+        }
+    }
+}
+
+/// GPU copies of `Meshes`, kept in step by `MeshUploadSystem`. Holds GPU
+/// handles, so it is a non-send resource.
+pub struct GpuMeshes {
+    meshes: MeshCache<GpuMesh>,
+}
+
+impl GpuMeshes {
+    pub fn new() -> Self {
+        Self { meshes: MeshCache::new() }
+    }
+
+    /// `None` until the mesh has been uploaded, which happens in the frame's
+    /// render stage.
+    pub fn get(&self, handle: MeshHandle) -> Option<&GpuMesh> {
+        self.meshes.get(handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.meshes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.meshes.len() == 0
+    }
+
+    /// Uploads meshes added or changed since the last call and frees those
+    /// removed. A changed mesh is written into its existing buffers when they
+    /// are large enough. Returns how many meshes were uploaded.
+    pub(crate) fn sync(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, meshes: &Meshes) -> usize {
+        self.meshes.sync(meshes, |mesh, previous| {
+            let (vertex_buffer, index_buffer) = match previous {
+                Some(gpu) => (Some(gpu.vertex_buffer), gpu.index_buffer.map(|(buffer, _)| buffer)),
+                None => (None, None),
+            };
+            let vertex_buffer = upload(
+                device,
+                queue,
+                vertex_buffer,
+                bytemuck::cast_slice(&mesh.vertices),
+                wgpu::BufferUsages::VERTEX,
+            );
+            let index_buffer = mesh.indices.as_ref().map(|indices| {
+                let buffer = upload(device, queue, index_buffer, indices.as_bytes(), wgpu::BufferUsages::INDEX);
+                (buffer, indices.format())
+            });
+            GpuMesh { vertex_buffer, index_buffer, draw_count: mesh.draw_count(), topology: mesh.topology }
+        })
+    }
+}
+
+impl Default for GpuMeshes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A value made from each mesh in `Meshes`, remembering which version of the
+/// mesh it was made from. The GPU-free half of `GpuMeshes`.
+struct MeshCache<T> {
+    entries: HashMap<MeshHandle, (u64, T)>,
+}
+
+impl<T> MeshCache<T> {
+    fn new() -> Self {
+        Self { entries: HashMap::new() }
+    }
+
+    fn get(&self, handle: MeshHandle) -> Option<&T> {
+        self.entries.get(&handle).map(|(_, value)| value)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drops the values of removed meshes, then remakes those of meshes added
+    /// or changed since, handing `make` the old value to reuse. Returns how
+    /// many were made.
+    fn sync(&mut self, meshes: &Meshes, mut make: impl FnMut(&Mesh, Option<T>) -> T) -> usize {
+        self.entries.retain(|&handle, _| meshes.contains(handle));
+
+        let mut made = 0;
+        for (handle, mesh, version) in meshes.versions() {
+            if self.entries.get(&handle).is_some_and(|(made_from, _)| *made_from == version) {
+                continue;
+            }
+            let previous = self.entries.remove(&handle).map(|(_, value)| value);
+            self.entries.insert(handle, (version, make(mesh, previous)));
+            made += 1;
+        }
+        made
+    }
+}
+
+/// Writes `contents` into `existing` if it fits, otherwise into a new buffer.
+fn upload(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    existing: Option<wgpu::Buffer>,
+    contents: &[u8],
+    usage: wgpu::BufferUsages,
+) -> wgpu::Buffer {
+    // Buffer writes must be a multiple of COPY_BUFFER_ALIGNMENT, which an odd
+    // number of u16 indices is not.
+    let padded = match contents.len() as u64 % wgpu::COPY_BUFFER_ALIGNMENT {
+        0 => Cow::Borrowed(contents),
+        remainder => {
+            let mut padded = contents.to_vec();
+            padded.resize(contents.len() + (wgpu::COPY_BUFFER_ALIGNMENT - remainder) as usize, 0);
+            Cow::Owned(padded)
+        }
+    };
+    match existing {
+        Some(buffer) if buffer.size() >= padded.len() as u64 => {
+            queue.write_buffer(&buffer, 0, &padded);
+            buffer
+        }
+        _ => device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh"),
+            contents: &padded,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        Mesh::indexed(
+            vec![Vertex::new([0.0, 0.0, 0.0]), Vertex::new([1.0, 0.0, 0.0]), Vertex::new([0.0, 1.0, 0.0])],
+            Indices::U16(vec![0, 1, 2]),
+        )
+    }
+
+    #[test]
+    fn removed_handles_stay_invalid_after_their_slot_is_reused() {
+        let mut meshes = Meshes::new();
+        let first = meshes.add(triangle());
+        assert!(meshes.remove(first).is_some());
+        assert!(meshes.remove(first).is_none());
+
+        let second = meshes.add(triangle().with_topology(wgpu::PrimitiveTopology::PointList));
+        assert_eq!(second.index, first.index);
+        assert_ne!(second, first);
+        assert!(meshes.get(first).is_none());
+        assert!(meshes.get_mut(first).is_none());
+        assert!(meshes.remove(first).is_none());
+        assert_eq!(meshes.get(second).unwrap().topology, wgpu::PrimitiveTopology::PointList);
+        assert_eq!(meshes.len(), 1);
+    }
+
+    #[test]
+    fn only_changed_meshes_count_as_new_versions() {
+        let mut meshes = Meshes::new();
+        let handle = meshes.add(triangle());
+        let version = |meshes: &Meshes| meshes.versions().next().unwrap().2;
+        let before = version(&meshes);
+        meshes.get(handle);
+        assert_eq!(version(&meshes), before);
+        meshes.get_mut(handle);
+        assert!(version(&meshes) > before);
+    }
+
+    #[test]
+    fn removed_meshes_are_dropped_on_the_next_sync() {
+        let mut meshes = Meshes::new();
+        let kept = meshes.add(triangle());
+        let removed = meshes.add(triangle());
+        // Counts how often each value was remade from the previous one.
+        let mut cache = MeshCache::<u32>::new();
+        let remake = |_: &Mesh, previous: Option<u32>| previous.map_or(0, |count| count + 1);
+
+        assert_eq!(cache.sync(&meshes, remake), 2);
+        assert_eq!(cache.sync(&meshes, remake), 0);
+
+        meshes.remove(removed);
+        meshes.get_mut(kept);
+        assert_eq!(cache.sync(&meshes, remake), 1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(removed), None);
+        assert_eq!(cache.get(kept), Some(&1));
+
+        // The reused slot is a new mesh, not a change to the removed one.
+        let added = meshes.add(triangle());
+        assert_eq!(cache.sync(&meshes, remake), 1);
+        assert_eq!(cache.get(added), Some(&0));
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    #[ignore = "needs a GPU adapter; run with --ignored"]
+    fn sync_reuses_buffers_that_fit_and_pads_indices() {
+        use crate::engine_core::wgpures::{EngineResources, WebGPUResources};
+
+        let gpu = pollster::block_on(WebGPUResources::new_offscreen(4, 4)).unwrap();
+        let (device, queue) = (gpu.get_device(), gpu.get_queue());
+        let mut meshes = Meshes::new();
+        let mut gpu_meshes = GpuMeshes::new();
+        let handle = meshes.add(triangle());
+
+        assert_eq!(gpu_meshes.sync(device, queue, &meshes), 1);
+        assert_eq!(gpu_meshes.sync(device, queue, &meshes), 0);
+        let uploaded = gpu_meshes.get(handle).unwrap();
+        // Three u16 indices are 6 bytes, padded to the copy alignment.
+        let (index_buffer, format) = uploaded.index_buffer().unwrap();
+        assert_eq!(index_buffer.size(), 8);
+        assert_eq!(format, wgpu::IndexFormat::Uint16);
+        assert_eq!(uploaded.draw_count(), 3);
+        let vertex_id = uploaded.vertex_buffer().global_id();
+        let index_id = index_buffer.global_id();
+
+        // Same size: written in place.
+        meshes.get_mut(handle).unwrap().vertices[0].color = [1.0, 0.0, 0.0, 1.0];
+        assert_eq!(gpu_meshes.sync(device, queue, &meshes), 1);
+        let uploaded = gpu_meshes.get(handle).unwrap();
+        assert_eq!(uploaded.vertex_buffer().global_id(), vertex_id);
+        assert_eq!(uploaded.index_buffer().unwrap().0.global_id(), index_id);
+
+        // Larger: new buffers.
+        let mesh = meshes.get_mut(handle).unwrap();
+        mesh.vertices.push(Vertex::new([1.0, 1.0, 0.0]));
+        mesh.indices = Some(Indices::U16(vec![0, 1, 2, 2, 1, 3]));
+        assert_eq!(gpu_meshes.sync(device, queue, &meshes), 1);
+        let uploaded = gpu_meshes.get(handle).unwrap();
+        assert_ne!(uploaded.vertex_buffer().global_id(), vertex_id);
+        assert_eq!(uploaded.index_buffer().unwrap().0.size(), 12);
+        assert_eq!(uploaded.draw_count(), 6);
+
+        meshes.remove(handle);
+        assert_eq!(gpu_meshes.sync(device, queue, &meshes), 0);
+        assert!(gpu_meshes.is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+// mesh_pass.rs
+// The graph pass drawing every visible `RenderableComponent`. There is no
+// camera yet, so `GlobalTransform` maps a mesh straight into clip space, and
+// without a depth buffer meshes land in query order.
+
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+use crate::components::renderable_component::RenderableComponent;
+use crate::components::transform_component::GlobalTransform;
+use crate::engine_core::mesh::{GpuMeshes, MeshHandle, Vertex};
+use crate::engine_core::render_graph::{PassContext, PassId, RenderGraph, ResourceId};
+
+const SHADER: &str = r#"
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) model_0: vec4<f32>,
+    @location(6) model_1: vec4<f32>,
+    @location(7) model_2: vec4<f32>,
+    @location(8) model_3: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    let model = mat4x4<f32>(in.model_0, in.model_1, in.model_2, in.model_3);
+    var out: VertexOutput;
+    out.position = model * vec4<f32>(in.position, 1.0);
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+/// Per-instance model matrix, one column per attribute.
+const INSTANCE_ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+    5 => Float32x4,
+    6 => Float32x4,
+    7 => Float32x4,
+    8 => Float32x4,
+];
+
+/// Pipelines and instance data of the `meshes` pass. Holds GPU handles, so it
+/// is a non-send resource, inserted by `RenderingSystem`.
+pub(crate) struct MeshPipelines {
+    format: wgpu::TextureFormat,
+    shader: Option<wgpu::ShaderModule>,
+    /// One per topology in use, made on first need.
+    pipelines: HashMap<wgpu::PrimitiveTopology, wgpu::RenderPipeline>,
+    instances: Option<wgpu::Buffer>,
+}
+
+impl MeshPipelines {
+    /// For drawing into targets of `format`.
+    pub(crate) fn new(format: wgpu::TextureFormat) -> Self {
+        Self { format, shader: None, pipelines: HashMap::new(), instances: None }
+    }
+
+    fn prepare(&mut self, device: &wgpu::Device, topology: wgpu::PrimitiveTopology) {
+        if self.pipelines.contains_key(&topology) {
+            return;
+        }
+        let shader = self.shader.get_or_insert_with(|| {
+            device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("mesh"),
+                source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+            })
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("mesh"),
+            layout: None,
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[
+                    Vertex::layout(),
+                    wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<[[f32; 4]; 4]>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &INSTANCE_ATTRIBUTES,
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: self.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState { topology, cull_mode: None, ..Default::default() },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        self.pipelines.insert(topology, pipeline);
+    }
+
+    /// Writes `matrices` into the instance buffer, growing it if needed.
+    fn write_instances(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, matrices: &[[[f32; 4]; 4]]) {
+        let contents: &[u8] = bytemuck::cast_slice(matrices);
+        match &self.instances {
+            Some(buffer) if buffer.size() >= contents.len() as u64 => queue.write_buffer(buffer, 0, contents),
+            _ => {
+                self.instances = Some(device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("mesh instances"),
+                    contents,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                }));
+            }
+        }
+    }
+}
+
+/// Adds the `meshes` pass, which draws over whatever earlier writers of
+/// `target` left there.
+pub(crate) fn add_mesh_pass(graph: &mut RenderGraph, target: ResourceId) -> PassId {
+    graph.add_pass("meshes").write(target).execute(move |ctx| draw_meshes(ctx, target))
+}
+
+fn draw_meshes(ctx: &mut PassContext, target: ResourceId) {
+    let world = ctx.world;
+    let (Some(gpu_meshes), Some(mut pipelines)) = (
+        world.resources.get_non_send::<GpuMeshes>(),
+        world.resources.get_non_send_mut::<MeshPipelines>(),
+    ) else {
+        return;
+    };
+
+    let draws: Vec<(MeshHandle, [[f32; 4]; 4])> = world
+        .query::<(&RenderableComponent, &GlobalTransform)>()
+        .iter()
+        .filter(|(_, (renderable, _))| renderable.visible && gpu_meshes.get(renderable.mesh).is_some())
+        .map(|(_, (renderable, global))| (renderable.mesh, global.0.to_cols_array_2d()))
+        .collect();
+    if draws.is_empty() {
+        return;
+    }
+
+    for (handle, _) in &draws {
+        let topology = gpu_meshes.get(*handle).expect("filtered above").topology();
+        pipelines.prepare(ctx.device, topology);
+    }
+    let matrices: Vec<[[f32; 4]; 4]> = draws.iter().map(|(_, matrix)| *matrix).collect();
+    pipelines.write_instances(ctx.device, ctx.queue, &matrices);
+
+    let mut pass = ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("meshes"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: ctx.texture_view(target),
+            resolve_target: None,
+            ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+        })],
+        depth_stencil_attachment: None,
+        timestamp_writes: None,
+        occlusion_query_set: None,
+    });
+    let instances = pipelines.instances.as_ref().expect("written above");
+    pass.set_vertex_buffer(1, instances.slice(..));
+    for (instance, (handle, _)) in draws.iter().enumerate() {
+        let mesh = gpu_meshes.get(*handle).expect("filtered above");
+        pass.set_pipeline(&pipelines.pipelines[&mesh.topology()]);
+        let instance = instance as u32;
+        mesh.draw(&mut pass, 0, instance..instance + 1);
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::components::renderable_component::RenderableComponent;
+    use crate::components::transform_component::Transform;
+    use crate::engine_core::mesh::{Mesh, Meshes, Vertex};
+    use crate::engine_core::wgpures::WebGPUResources;
+    use crate::LuminaEngine;
+
+    fn pixels(engine: &LuminaEngine) -> Vec<[u8; 4]> {
+        let gpu = engine.world().resources.get_non_send::<WebGPUResources>().unwrap();
+        gpu.read_frame().unwrap().pixels().map(|pixel| pixel.0).collect()
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter; run with --ignored"]
+    fn visible_meshes_are_drawn_over_the_clear() {
+        let mut engine = pollster::block_on(LuminaEngine::offscreen(4, 4)).unwrap();
+        // Covers all of clip space.
+        let green = [0.0, 1.0, 0.0, 1.0];
+        let mesh = Mesh::new(vec![
+            Vertex::new([-1.0, -1.0, 0.0]).with_color(green),
+            Vertex::new([3.0, -1.0, 0.0]).with_color(green),
+            Vertex::new([-1.0, 3.0, 0.0]).with_color(green),
+        ]);
+        let handle = engine.world().resource_mut::<Meshes>().unwrap().add(mesh);
+        let entity = engine.world_mut().spawn((Transform::IDENTITY, RenderableComponent::new(handle)));
+
+        engine.update();
+        assert!(pixels(&engine).iter().all(|pixel| *pixel == [0, 255, 0, 255]));
+
+        engine.world_mut().insert(entity, RenderableComponent { visible: false, ..RenderableComponent::new(handle) });
+        engine.update();
+        assert!(pixels(&engine).iter().all(|pixel| *pixel == [0, 0, 0, 255]));
+    }
+}
@@ -5,4 +5,12 @@ pub mod temporal;
 pub mod networking;
 pub mod rendering;
 pub mod webworker;
-pub mod inputhandler;
\ No newline at end of file
+pub mod inputhandler;
+pub mod scene_graph;
+pub mod fixed_time;
+pub mod clock;
+pub mod replay;
+pub mod renderer_config;
+pub mod render_graph;
+pub mod mesh;
+pub mod mesh_pass;
\ No newline at end of file
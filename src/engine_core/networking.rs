@@ -6,4 +6,10 @@ impl NetworkResources {
     pub fn new() -> Self {
         Self { }
     }
-}
\ No newline at end of file
+}
+
+impl Default for NetworkResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,747 @@
+// render_graph.rs
+use std::collections::BTreeSet;
+use std::fmt::{self, Write as _};
+use crate::engine_core::world::World;
+
+/// Handle to a texture or buffer in a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(usize);
+
+/// Handle to a pass in a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PassId(usize);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextureDesc {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub usage: wgpu::TextureUsages,
+    pub sample_count: u32,
+}
+
+impl TextureDesc {
+    /// A single-sampled attachment that later passes can also sample from.
+    pub fn new(width: u32, height: u32, format: wgpu::TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            sample_count: 1,
+        }
+    }
+
+    pub fn with_usage(mut self, usage: wgpu::TextureUsages) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferDesc {
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+/// What a transient resource needs to be created. Two transient resources
+/// with equal descriptions and disjoint lifetimes share one physical resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PhysicalDesc {
+    Texture(TextureDesc),
+    Buffer(BufferDesc),
+}
+
+#[derive(Clone, Debug)]
+enum ResourceKind {
+    /// Created and owned by the graph.
+    Transient(PhysicalDesc),
+    /// Supplied to `execute` each frame, e.g. the swapchain view.
+    ImportedTexture,
+    ImportedBuffer,
+}
+
+struct ResourceNode {
+    name: String,
+    kind: ResourceKind,
+    /// Read after the graph runs, so its pass must not be culled nor its
+    /// memory reused.
+    output: bool,
+}
+
+type PassFn = Box<dyn FnMut(&mut PassContext) + Send + Sync>;
+
+struct PassNode {
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effect: bool,
+    run: PassFn,
+}
+
+/// Passes and the resources they read and write, from which `compile` works
+/// out an execution order.
+///
+/// A pass that reads a resource runs after every pass that writes it, and
+/// writers of the same resource run in the order they were added, so a read
+/// always sees the resource's final contents. Passes that nothing needed is
+/// derived from are culled: a pass is needed if it writes an imported or
+/// output resource, is marked `side_effect`, or writes something a needed pass
+/// reads.
+pub struct RenderGraph {
+    resources: Vec<ResourceNode>,
+    passes: Vec<PassNode>,
+    /// Cleared whenever the graph changes.
+    compiled: Option<CompiledGraph>,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        Self { resources: Vec::new(), passes: Vec::new(), compiled: None }
+    }
+
+    /// A texture the graph creates and may alias with other transient ones.
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Transient(PhysicalDesc::Texture(desc)))
+    }
+
+    pub fn create_buffer(&mut self, name: &str, desc: BufferDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Transient(PhysicalDesc::Buffer(desc)))
+    }
+
+    /// A texture view bound with `execute` each frame.
+    pub fn import_texture(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedTexture)
+    }
+
+    pub fn import_buffer(&mut self, name: &str) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedBuffer)
+    }
+
+    /// Keeps the passes producing `resource`, and its memory, intact even
+    /// though no pass reads it. Imported resources are always outputs.
+    pub fn mark_output(&mut self, resource: ResourceId) {
+        self.resources[resource.0].output = true;
+        self.compiled = None;
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) -> ResourceId {
+        self.resources.push(ResourceNode { name: name.to_string(), kind, output: false });
+        self.compiled = None;
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// Starts declaring a pass; finish with `PassBuilder::execute`.
+    pub fn add_pass(&mut self, name: &str) -> PassBuilder<'_> {
+        PassBuilder {
+            graph: self,
+            name: name.to_string(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+            side_effect: false,
+        }
+    }
+
+    pub fn resource_name(&self, resource: ResourceId) -> &str {
+        &self.resources[resource.0].name
+    }
+
+    pub fn pass_name(&self, pass: PassId) -> &str {
+        &self.passes[pass.0].name
+    }
+
+    pub fn pass_count(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passes.is_empty()
+    }
+
+    /// Orders and culls passes and assigns transient resources to physical
+    /// ones. Needs no GPU.
+    pub fn compile(&self) -> Result<CompiledGraph, RenderGraphError> {
+        let pass_count = self.passes.len();
+
+        let mut last_writer: Vec<Option<usize>> = vec![None; self.resources.len()];
+        let mut dependencies: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); pass_count];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &resource in &pass.writes {
+                if let Some(previous) = last_writer[resource.0].replace(index) {
+                    dependencies[index].insert(previous);
+                }
+            }
+        }
+        for (index, pass) in self.passes.iter().enumerate() {
+            for &resource in &pass.reads {
+                if pass.writes.contains(&resource) {
+                    continue;
+                }
+                if let Some(writer) = last_writer[resource.0] {
+                    dependencies[index].insert(writer);
+                }
+            }
+        }
+
+        let mut needed = vec![false; pass_count];
+        let mut stack: Vec<usize> = (0..pass_count)
+            .filter(|&index| {
+                let pass = &self.passes[index];
+                pass.side_effect || pass.writes.iter().any(|&resource| self.is_output(resource))
+            })
+            .collect();
+        while let Some(index) = stack.pop() {
+            if std::mem::replace(&mut needed[index], true) {
+                continue;
+            }
+            stack.extend(dependencies[index].iter().copied());
+        }
+
+        // Kahn's algorithm over the needed passes, taking the earliest-added
+        // ready pass first so the order is stable.
+        let mut waiting_on: Vec<usize> = (0..pass_count).map(|index| dependencies[index].len()).collect();
+        let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); pass_count];
+        for (index, deps) in dependencies.iter().enumerate() {
+            for &dependency in deps {
+                dependents[dependency].push(index);
+            }
+        }
+        let mut ready: BTreeSet<usize> = (0..pass_count).filter(|&index| needed[index] && waiting_on[index] == 0).collect();
+        let mut order = Vec::new();
+        while let Some(index) = ready.pop_first() {
+            order.push(PassId(index));
+            for &dependent in &dependents[index] {
+                waiting_on[dependent] -= 1;
+                if needed[dependent] && waiting_on[dependent] == 0 {
+                    ready.insert(dependent);
+                }
+            }
+        }
+        let scheduled = needed.iter().filter(|&&needed| needed).count();
+        if order.len() != scheduled {
+            let passes = (0..pass_count)
+                .filter(|&index| needed[index] && !order.contains(&PassId(index)))
+                .map(|index| self.passes[index].name.clone())
+                .collect();
+            return Err(RenderGraphError::Cycle { passes });
+        }
+        let culled = (0..pass_count).filter(|&index| !needed[index]).map(PassId).collect();
+
+        let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+        for (position, pass) in order.iter().enumerate() {
+            let pass = &self.passes[pass.0];
+            for &resource in pass.reads.iter().chain(&pass.writes) {
+                let lifetime = lifetimes[resource.0].get_or_insert((position, position));
+                lifetime.1 = position;
+            }
+        }
+
+        // Greedy aliasing: each transient resource, by first use, takes the
+        // first slot with the same description that is free by then.
+        let mut by_first_use: Vec<usize> = (0..self.resources.len()).filter(|&index| lifetimes[index].is_some()).collect();
+        by_first_use.sort_by_key(|&index| lifetimes[index].map(|(first, _)| first));
+        let mut slots: Vec<PhysicalDesc> = Vec::new();
+        let mut slot_free_after: Vec<Option<usize>> = Vec::new();
+        let mut slot_of = vec![None; self.resources.len()];
+        for index in by_first_use {
+            let ResourceKind::Transient(desc) = &self.resources[index].kind else { continue };
+            let (first, last) = lifetimes[index].expect("filtered above");
+            let free_after = if self.resources[index].output { None } else { Some(last) };
+            let reusable = (0..slots.len()).find(|&slot| {
+                slots[slot] == *desc && slot_free_after[slot].is_some_and(|free_after| free_after < first)
+            });
+            let slot = match reusable {
+                Some(slot) => slot,
+                None => {
+                    slots.push(desc.clone());
+                    slot_free_after.push(None);
+                    slots.len() - 1
+                }
+            };
+            slot_free_after[slot] = free_after;
+            slot_of[index] = Some(slot);
+        }
+
+        Ok(CompiledGraph { order, culled, slots, slot_of, lifetimes })
+    }
+
+    fn is_output(&self, resource: ResourceId) -> bool {
+        let node = &self.resources[resource.0];
+        node.output || !matches!(node.kind, ResourceKind::Transient(_))
+    }
+
+    /// Runs the needed passes in order, one command encoder each, and submits
+    /// them together. Compiles first if the graph changed since the last run.
+    /// Every imported resource a needed pass uses must be bound in `imports`.
+    /// Passes can read `world`, e.g. to find what to draw.
+    pub fn execute(
+        &mut self,
+        world: &World,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        resources: &mut GraphResources,
+        imports: &[(ResourceId, BoundResource<'_>)],
+    ) -> Result<(), RenderGraphError> {
+        if self.compiled.is_none() {
+            self.compiled = Some(self.compile()?);
+        }
+        let compiled = self.compiled.as_ref().expect("compiled above");
+        resources.allocate(device, compiled);
+
+        let mut bindings: Vec<Option<BoundResource<'_>>> = vec![None; self.resources.len()];
+        for &(resource, bound) in imports {
+            bindings[resource.0] = Some(bound);
+        }
+        for (index, slot) in compiled.slot_of.iter().enumerate() {
+            if let Some(slot) = slot {
+                bindings[index] = Some(resources.bound(*slot));
+            }
+        }
+        for pass in &compiled.order {
+            let pass = &self.passes[pass.0];
+            if let Some(&missing) = pass.reads.iter().chain(&pass.writes).find(|resource| bindings[resource.0].is_none()) {
+                return Err(RenderGraphError::MissingImport(self.resources[missing.0].name.clone()));
+            }
+        }
+
+        let mut command_buffers = Vec::with_capacity(compiled.order.len());
+        for pass in &compiled.order {
+            let pass = &mut self.passes[pass.0];
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some(pass.name.as_str()),
+            });
+            let mut context = PassContext {
+                world,
+                device,
+                queue,
+                encoder: &mut encoder,
+                pass_name: &pass.name,
+                reads: &pass.reads,
+                writes: &pass.writes,
+                bindings: &bindings,
+            };
+            (pass.run)(&mut context);
+            command_buffers.push(encoder.finish());
+        }
+        queue.submit(command_buffers);
+        Ok(())
+    }
+
+    /// Graphviz view of the graph: passes are boxes in execution order (culled
+    /// ones dashed), resources are ellipses labelled with their physical slot.
+    pub fn to_dot(&self) -> String {
+        let compiled = self.compile().ok();
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+        for (index, pass) in self.passes.iter().enumerate() {
+            let position = compiled.as_ref().and_then(|compiled| compiled.position(PassId(index)));
+            let (label, style) = match position {
+                Some(position) => (format!("{}. {}", position, pass.name), "solid"),
+                None => (pass.name.clone(), "dashed"),
+            };
+            let _ = writeln!(dot, "    p{} [shape=box, style={}, label={:?}];", index, style, label);
+        }
+        for (index, resource) in self.resources.iter().enumerate() {
+            let slot = compiled.as_ref().and_then(|compiled| compiled.slot_of[index]);
+            let label = match (&resource.kind, slot) {
+                (ResourceKind::Transient(_), Some(slot)) => format!("{}\\nslot {}", resource.name, slot),
+                (ResourceKind::Transient(_), None) => resource.name.clone(),
+                _ => format!("{}\\nimported", resource.name),
+            };
+            let _ = writeln!(dot, "    r{} [shape=ellipse, label=\"{}\"];", index, label.replace('"', "\\\""));
+        }
+        for (index, pass) in self.passes.iter().enumerate() {
+            for resource in &pass.reads {
+                let _ = writeln!(dot, "    r{} -> p{};", resource.0, index);
+            }
+            for resource in &pass.writes {
+                let _ = writeln!(dot, "    p{} -> r{};", index, resource.0);
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The same information as `to_dot`, as JSON. Includes the compile error
+    /// if there is one.
+    pub fn to_json(&self) -> serde_json::Value {
+        let compiled = self.compile();
+        let ok = compiled.as_ref().ok();
+        let names = |resources: &[ResourceId]| -> Vec<&str> {
+            resources.iter().map(|resource| self.resources[resource.0].name.as_str()).collect()
+        };
+        let passes: Vec<serde_json::Value> = self
+            .passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                serde_json::json!({
+                    "name": pass.name,
+                    "reads": names(&pass.reads),
+                    "writes": names(&pass.writes),
+                    "side_effect": pass.side_effect,
+                    "position": ok.and_then(|compiled| compiled.position(PassId(index))),
+                })
+            })
+            .collect();
+        let resources: Vec<serde_json::Value> = self
+            .resources
+            .iter()
+            .enumerate()
+            .map(|(index, resource)| {
+                let kind = match &resource.kind {
+                    ResourceKind::Transient(PhysicalDesc::Texture(desc)) => format!(
+                        "texture {}x{} {:?}",
+                        desc.width, desc.height, desc.format
+                    ),
+                    ResourceKind::Transient(PhysicalDesc::Buffer(desc)) => format!("buffer {} bytes", desc.size),
+                    ResourceKind::ImportedTexture => "imported texture".to_string(),
+                    ResourceKind::ImportedBuffer => "imported buffer".to_string(),
+                };
+                serde_json::json!({
+                    "name": resource.name,
+                    "kind": kind,
+                    "output": self.is_output(ResourceId(index)),
+                    "slot": ok.and_then(|compiled| compiled.slot_of[index]),
+                    "lifetime": ok.and_then(|compiled| compiled.lifetimes[index]),
+                })
+            })
+            .collect();
+        serde_json::json!({
+            "passes": passes,
+            "resources": resources,
+            "error": compiled.err().map(|error| error.to_string()),
+        })
+    }
+}
+
+impl Default for RenderGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Declares what a pass touches. Writing a resource implies it may also be
+/// read, as with blending or a depth test; declaring only `read` is enough
+/// for resources the pass never writes.
+pub struct PassBuilder<'g> {
+    graph: &'g mut RenderGraph,
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    side_effect: bool,
+}
+
+impl<'g> PassBuilder<'g> {
+    pub fn read(mut self, resource: ResourceId) -> Self {
+        if !self.reads.contains(&resource) {
+            self.reads.push(resource);
+        }
+        self
+    }
+
+    pub fn write(mut self, resource: ResourceId) -> Self {
+        if !self.writes.contains(&resource) {
+            self.writes.push(resource);
+        }
+        self
+    }
+
+    /// Never culled, for passes whose effect the graph cannot see (queries,
+    /// readbacks).
+    pub fn side_effect(mut self) -> Self {
+        self.side_effect = true;
+        self
+    }
+
+    /// Adds the pass; `run` records its commands each frame.
+    pub fn execute<F>(self, run: F) -> PassId
+    where
+        F: FnMut(&mut PassContext) + Send + Sync + 'static,
+    {
+        let graph = self.graph;
+        graph.passes.push(PassNode {
+            name: self.name,
+            reads: self.reads,
+            writes: self.writes,
+            side_effect: self.side_effect,
+            run: Box::new(run),
+        });
+        graph.compiled = None;
+        PassId(graph.passes.len() - 1)
+    }
+}
+
+/// Result of `RenderGraph::compile`.
+#[derive(Clone, Debug)]
+pub struct CompiledGraph {
+    order: Vec<PassId>,
+    culled: Vec<PassId>,
+    slots: Vec<PhysicalDesc>,
+    slot_of: Vec<Option<usize>>,
+    /// First and last position in `order` that uses each resource.
+    lifetimes: Vec<Option<(usize, usize)>>,
+}
+
+impl CompiledGraph {
+    /// Passes to run, in order.
+    pub fn order(&self) -> &[PassId] {
+        &self.order
+    }
+
+    pub fn culled(&self) -> &[PassId] {
+        &self.culled
+    }
+
+    /// Transient resources to create; fewer than declared when some alias.
+    pub fn physical_resources(&self) -> &[PhysicalDesc] {
+        &self.slots
+    }
+
+    /// Index into `physical_resources` backing a transient resource. `None`
+    /// for imported resources and ones no remaining pass uses.
+    pub fn slot(&self, resource: ResourceId) -> Option<usize> {
+        self.slot_of[resource.0]
+    }
+
+    /// First and last position in `order` that touches `resource`.
+    pub fn lifetime(&self, resource: ResourceId) -> Option<(usize, usize)> {
+        self.lifetimes[resource.0]
+    }
+
+    fn position(&self, pass: PassId) -> Option<usize> {
+        self.order.iter().position(|&scheduled| scheduled == pass)
+    }
+}
+
+/// A texture view or buffer given to a pass.
+#[derive(Clone, Copy)]
+pub enum BoundResource<'a> {
+    Texture(&'a wgpu::TextureView),
+    Buffer(&'a wgpu::Buffer),
+}
+
+/// What a pass gets to record its commands with.
+pub struct PassContext<'a> {
+    /// Resources the running system already holds, such as `RenderSystem`
+    /// and `WebGPUResources`, panic if borrowed again.
+    pub world: &'a World,
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pass_name: &'a str,
+    reads: &'a [ResourceId],
+    writes: &'a [ResourceId],
+    bindings: &'a [Option<BoundResource<'a>>],
+}
+
+impl<'a> PassContext<'a> {
+    /// Panics if the pass did not declare `resource` or it is a buffer.
+    pub fn texture_view(&self, resource: ResourceId) -> &'a wgpu::TextureView {
+        match self.binding(resource) {
+            BoundResource::Texture(view) => view,
+            BoundResource::Buffer(_) => panic!("pass `{}` asked for buffer {:?} as a texture", self.pass_name, resource),
+        }
+    }
+
+    /// Panics if the pass did not declare `resource` or it is a texture.
+    pub fn buffer(&self, resource: ResourceId) -> &'a wgpu::Buffer {
+        match self.binding(resource) {
+            BoundResource::Buffer(buffer) => buffer,
+            BoundResource::Texture(_) => panic!("pass `{}` asked for texture {:?} as a buffer", self.pass_name, resource),
+        }
+    }
+
+    fn binding(&self, resource: ResourceId) -> BoundResource<'a> {
+        if !self.reads.contains(&resource) && !self.writes.contains(&resource) {
+            panic!("pass `{}` did not declare {:?}", self.pass_name, resource);
+        }
+        self.bindings[resource.0].expect("bindings are checked before passes run")
+    }
+}
+
+enum PhysicalResource {
+    Texture { _texture: wgpu::Texture, view: wgpu::TextureView },
+    Buffer(wgpu::Buffer),
+}
+
+/// The GPU side of a `RenderGraph`: physical textures and buffers for its
+/// transient resources, kept across frames and recreated only when their
+/// description changes. Holds GPU handles, so it is a non-send resource.
+pub struct GraphResources {
+    slots: Vec<(PhysicalDesc, PhysicalResource)>,
+}
+
+impl GraphResources {
+    pub fn new() -> Self {
+        Self { slots: Vec::new() }
+    }
+
+    fn allocate(&mut self, device: &wgpu::Device, compiled: &CompiledGraph) {
+        self.slots.truncate(compiled.slots.len());
+        for (index, desc) in compiled.slots.iter().enumerate() {
+            if self.slots.get(index).is_some_and(|(existing, _)| existing == desc) {
+                continue;
+            }
+            let resource = create_physical(device, desc, index);
+            if index < self.slots.len() {
+                self.slots[index] = (desc.clone(), resource);
+            } else {
+                self.slots.push((desc.clone(), resource));
+            }
+        }
+    }
+
+    fn bound(&self, slot: usize) -> BoundResource<'_> {
+        match &self.slots[slot].1 {
+            PhysicalResource::Texture { view, .. } => BoundResource::Texture(view),
+            PhysicalResource::Buffer(buffer) => BoundResource::Buffer(buffer),
+        }
+    }
+}
+
+impl Default for GraphResources {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn create_physical(device: &wgpu::Device, desc: &PhysicalDesc, slot: usize) -> PhysicalResource {
+    let label = format!("render graph slot {}", slot);
+    match desc {
+        PhysicalDesc::Texture(desc) => {
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(&label),
+                size: wgpu::Extent3d { width: desc.width, height: desc.height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: desc.sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: desc.format,
+                usage: desc.usage,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            PhysicalResource::Texture { _texture: texture, view }
+        }
+        PhysicalDesc::Buffer(desc) => PhysicalResource::Buffer(device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&label),
+            size: desc.size,
+            usage: desc.usage,
+            mapped_at_creation: false,
+        })),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderGraphError {
+    /// The listed passes depend on each other in a loop.
+    Cycle { passes: Vec<String> },
+    /// A needed pass uses an imported resource `execute` was not given.
+    MissingImport(String),
+}
+
+impl fmt::Display for RenderGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderGraphError::Cycle { passes } => write!(f, "render graph cycle among passes: {}", passes.join(", ")),
+            RenderGraphError::MissingImport(name) => write!(f, "imported resource `{}` was not bound", name),
+        }
+    }
+}
+
+impl std::error::Error for RenderGraphError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texture(graph: &mut RenderGraph, name: &str) -> ResourceId {
+        graph.create_texture(name, TextureDesc::new(64, 64, wgpu::TextureFormat::Rgba16Float))
+    }
+
+    fn names(graph: &RenderGraph, passes: &[PassId]) -> Vec<String> {
+        passes.iter().map(|&pass| graph.pass_name(pass).to_string()).collect()
+    }
+
+    #[test]
+    fn readers_run_after_writers_added_later() {
+        let mut graph = RenderGraph::new();
+        let backbuffer = graph.import_texture("backbuffer");
+        let hdr = texture(&mut graph, "hdr");
+        graph.add_pass("tonemap").read(hdr).write(backbuffer).execute(|_| {});
+        graph.add_pass("scene").write(hdr).execute(|_| {});
+        graph.add_pass("overlay").write(backbuffer).execute(|_| {});
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(names(&graph, compiled.order()), ["scene", "tonemap", "overlay"]);
+        assert!(compiled.culled().is_empty());
+    }
+
+    #[test]
+    fn passes_nothing_needs_are_culled() {
+        let mut graph = RenderGraph::new();
+        let backbuffer = graph.import_texture("backbuffer");
+        let unused = texture(&mut graph, "unused");
+        let debug = graph.add_pass("debug").write(unused).execute(|_| {});
+        graph.add_pass("present").write(backbuffer).execute(|_| {});
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(names(&graph, compiled.order()), ["present"]);
+        assert_eq!(compiled.culled(), [debug]);
+        assert_eq!(compiled.slot(unused), None);
+        assert!(compiled.physical_resources().is_empty());
+
+        graph.mark_output(unused);
+        assert!(graph.compile().unwrap().culled().is_empty());
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_a_slot() {
+        let mut graph = RenderGraph::new();
+        let backbuffer = graph.import_texture("backbuffer");
+        let a = texture(&mut graph, "a");
+        let b = graph.create_texture("b", TextureDesc::new(64, 64, wgpu::TextureFormat::Rgba8Unorm));
+        let c = texture(&mut graph, "c");
+        graph.add_pass("first").write(a).execute(|_| {});
+        graph.add_pass("second").read(a).write(b).execute(|_| {});
+        graph.add_pass("third").read(b).write(c).execute(|_| {});
+        graph.add_pass("last").read(c).write(backbuffer).execute(|_| {});
+
+        let compiled = graph.compile().unwrap();
+        assert_eq!(compiled.lifetime(a), Some((0, 1)));
+        assert_eq!(compiled.lifetime(c), Some((2, 3)));
+        assert_eq!(compiled.physical_resources().len(), 2);
+        assert_eq!(compiled.slot(a), compiled.slot(c));
+        assert_ne!(compiled.slot(a), compiled.slot(b));
+    }
+
+    #[test]
+    fn overlapping_lifetimes_do_not_alias() {
+        let mut graph = RenderGraph::new();
+        let backbuffer = graph.import_texture("backbuffer");
+        let a = texture(&mut graph, "a");
+        let b = texture(&mut graph, "b");
+        graph.add_pass("first").write(a).execute(|_| {});
+        graph.add_pass("second").write(b).execute(|_| {});
+        graph.add_pass("combine").read(a).read(b).write(backbuffer).execute(|_| {});
+
+        let compiled = graph.compile().unwrap();
+        assert_ne!(compiled.slot(a), compiled.slot(b));
+    }
+
+    #[test]
+    fn mutual_reads_are_a_cycle() {
+        let mut graph = RenderGraph::new();
+        let x = texture(&mut graph, "x");
+        let y = texture(&mut graph, "y");
+        graph.add_pass("ping").read(x).write(y).side_effect().execute(|_| {});
+        graph.add_pass("pong").read(y).write(x).side_effect().execute(|_| {});
+
+        let Err(RenderGraphError::Cycle { passes }) = graph.compile() else { panic!("expected a cycle") };
+        assert_eq!(passes, ["ping", "pong"]);
+    }
+}
@@ -0,0 +1,340 @@
+// renderer_config.rs
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+/// How `WebGPUResources` picks its adapter, device and presentation. Every
+/// field has a default, so a JSON file only needs the ones it changes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RendererConfig {
+    /// Graphics APIs to look for adapters on. Empty means every one this
+    /// build supports.
+    pub backends: Vec<GpuBackend>,
+    pub power_preference: PowerPreference,
+    pub present_mode: PresentMode,
+    /// Device creation fails if the adapter lacks any of these.
+    pub required_features: Vec<GpuFeature>,
+    /// Enabled when the adapter has them; check `Device::features` before use.
+    pub optional_features: Vec<GpuFeature>,
+    pub limits: LimitsPreset,
+    /// Retry with WebGL2-level limits when the adapter cannot meet `limits`.
+    pub webgl2_fallback: bool,
+    /// Composite the canvas with the page behind it instead of opaquely.
+    pub transparent: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            backends: Vec::new(),
+            power_preference: PowerPreference::Default,
+            present_mode: PresentMode::Vsync,
+            required_features: Vec::new(),
+            optional_features: Vec::new(),
+            limits: LimitsPreset::Default,
+            webgl2_fallback: true,
+            transparent: false,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    pub(crate) fn wgpu_backends(&self) -> wgpu::Backends {
+        match self.backends.as_slice() {
+            [] => wgpu::Backends::all(),
+            backends => backends.iter().fold(wgpu::Backends::empty(), |all, backend| all | backend.to_wgpu()),
+        }
+    }
+
+    pub(crate) fn required_wgpu_features(&self) -> wgpu::Features {
+        to_wgpu_features(&self.required_features)
+    }
+
+    pub(crate) fn optional_wgpu_features(&self) -> wgpu::Features {
+        to_wgpu_features(&self.optional_features)
+    }
+}
+
+fn to_wgpu_features(features: &[GpuFeature]) -> wgpu::Features {
+    features.iter().fold(wgpu::Features::empty(), |all, feature| all | feature.to_wgpu())
+}
+
+/// Only those compiled into this build are used; natively that is every
+/// backend but `BrowserWebGpu`, in the browser only `BrowserWebGpu`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GpuBackend {
+    Vulkan,
+    Metal,
+    Dx12,
+    /// OpenGL ES natively.
+    Gl,
+    BrowserWebGpu,
+}
+
+impl GpuBackend {
+    pub fn to_wgpu(self) -> wgpu::Backends {
+        match self {
+            GpuBackend::Vulkan => wgpu::Backends::VULKAN,
+            GpuBackend::Metal => wgpu::Backends::METAL,
+            GpuBackend::Dx12 => wgpu::Backends::DX12,
+            GpuBackend::Gl => wgpu::Backends::GL,
+            GpuBackend::BrowserWebGpu => wgpu::Backends::BROWSER_WEBGPU,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerPreference {
+    /// Let the platform decide.
+    Default,
+    /// Prefer an integrated GPU.
+    LowPower,
+    /// Prefer a discrete GPU.
+    HighPerformance,
+}
+
+impl PowerPreference {
+    pub fn to_wgpu(self) -> wgpu::PowerPreference {
+        match self {
+            PowerPreference::Default => wgpu::PowerPreference::None,
+            PowerPreference::LowPower => wgpu::PowerPreference::LowPower,
+            PowerPreference::HighPerformance => wgpu::PowerPreference::HighPerformance,
+        }
+    }
+}
+
+/// Falls back to `Vsync`, which every surface supports, when the requested
+/// mode is not available.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PresentMode {
+    /// Waits for vertical blank; no tearing, frame rate capped at the display's.
+    Vsync,
+    /// No tearing and no cap; frames that miss a blank are replaced.
+    Mailbox,
+    /// Presents at once; lowest latency, may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Vsync => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+
+    /// This mode if the surface supports it, vsync otherwise.
+    pub fn choose(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        let mode = self.to_wgpu();
+        if supported.contains(&mode) {
+            return mode;
+        }
+        tracing::warn!("present mode {:?} is not supported; using vsync", self);
+        wgpu::PresentMode::Fifo
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LimitsPreset {
+    /// What every WebGPU implementation supports.
+    Default,
+    /// Enough for most adapters, including older ones.
+    Downlevel,
+    /// What a WebGL2 context can offer.
+    WebGl2,
+}
+
+impl LimitsPreset {
+    pub fn to_wgpu(self) -> wgpu::Limits {
+        match self {
+            LimitsPreset::Default => wgpu::Limits::default(),
+            LimitsPreset::Downlevel => wgpu::Limits::downlevel_defaults(),
+            LimitsPreset::WebGl2 => wgpu::Limits::downlevel_webgl2_defaults(),
+        }
+    }
+}
+
+/// Optional GPU features the engine knows how to request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GpuFeature {
+    TimestampQuery,
+    TextureCompressionBc,
+    TextureCompressionEtc2,
+    TextureCompressionAstc,
+    DepthClipControl,
+    IndirectFirstInstance,
+    ShaderF16,
+    Float32Filterable,
+}
+
+impl GpuFeature {
+    pub fn to_wgpu(self) -> wgpu::Features {
+        match self {
+            GpuFeature::TimestampQuery => wgpu::Features::TIMESTAMP_QUERY,
+            GpuFeature::TextureCompressionBc => wgpu::Features::TEXTURE_COMPRESSION_BC,
+            GpuFeature::TextureCompressionEtc2 => wgpu::Features::TEXTURE_COMPRESSION_ETC2,
+            GpuFeature::TextureCompressionAstc => wgpu::Features::TEXTURE_COMPRESSION_ASTC,
+            GpuFeature::DepthClipControl => wgpu::Features::DEPTH_CLIP_CONTROL,
+            GpuFeature::IndirectFirstInstance => wgpu::Features::INDIRECT_FIRST_INSTANCE,
+            GpuFeature::ShaderF16 => wgpu::Features::SHADER_F16,
+            GpuFeature::Float32Filterable => wgpu::Features::FLOAT32_FILTERABLE,
+        }
+    }
+}
+
+/// A limit the adapter cannot meet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FailedLimit {
+    pub name: &'static str,
+    pub requested: u64,
+    pub available: u64,
+}
+
+/// Everything a `RendererConfig` asked for that the adapter lacks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CapabilityReport {
+    pub adapter: String,
+    pub missing_features: Vec<GpuFeature>,
+    pub failed_limits: Vec<FailedLimit>,
+}
+
+impl CapabilityReport {
+    /// Checks `config` against `adapter`. With `webgl2_fallback`, limits only
+    /// fail if the WebGL2 ones do as well; the limits to use are returned along
+    /// with the report.
+    pub(crate) fn check(config: &RendererConfig, adapter: &wgpu::Adapter) -> (Self, wgpu::Limits) {
+        Self::check_against(config, adapter.get_info().name, adapter.features(), &adapter.limits())
+    }
+
+    fn check_against(
+        config: &RendererConfig,
+        adapter: String,
+        available_features: wgpu::Features,
+        available_limits: &wgpu::Limits,
+    ) -> (Self, wgpu::Limits) {
+        let missing_features = config
+            .required_features
+            .iter()
+            .copied()
+            .filter(|feature| !available_features.contains(feature.to_wgpu()))
+            .collect();
+
+        let mut limits = config.limits.to_wgpu();
+        if config.webgl2_fallback && !limits.check_limits(available_limits) {
+            let webgl2 = wgpu::Limits::downlevel_webgl2_defaults().using_resolution(available_limits.clone());
+            if webgl2.check_limits(available_limits) {
+                tracing::warn!("adapter cannot meet {:?} limits; falling back to WebGL2 limits", config.limits);
+                limits = webgl2;
+            }
+        }
+        let mut failed_limits = Vec::new();
+        limits.check_limits_with_fail_fn(available_limits, false, |name, requested, available| {
+            failed_limits.push(FailedLimit { name, requested, available });
+        });
+
+        let report = Self { adapter, missing_features, failed_limits };
+        (report, limits)
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.missing_features.is_empty() && self.failed_limits.is_empty()
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "adapter `{}` lacks", self.adapter)?;
+        let mut first = true;
+        for feature in &self.missing_features {
+            write!(f, "{} feature {:?}", if first { "" } else { "," }, feature)?;
+            first = false;
+        }
+        for limit in &self.failed_limits {
+            write!(
+                f,
+                "{} {} (requested {}, available {})",
+                if first { "" } else { "," },
+                limit.name,
+                limit.requested,
+                limit.available
+            )?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_default_to_all_and_load_from_json() {
+        assert_eq!(RendererConfig::default().wgpu_backends(), wgpu::Backends::all());
+
+        let config = RendererConfig::from_json(r#"{ "backends": ["Vulkan", "Gl"] }"#).unwrap();
+        assert_eq!(config.wgpu_backends(), wgpu::Backends::VULKAN | wgpu::Backends::GL);
+        assert_eq!(config.limits, LimitsPreset::Default);
+    }
+
+    fn check(config: &RendererConfig, features: wgpu::Features, limits: &wgpu::Limits) -> (CapabilityReport, wgpu::Limits) {
+        CapabilityReport::check_against(config, "test adapter".to_string(), features, limits)
+    }
+
+    #[test]
+    fn capable_adapter_passes_with_the_requested_limits() {
+        let config = RendererConfig { required_features: vec![GpuFeature::TimestampQuery], ..Default::default() };
+        let (report, limits) = check(&config, wgpu::Features::TIMESTAMP_QUERY, &wgpu::Limits::default());
+        assert!(report.is_supported());
+        assert_eq!(limits, wgpu::Limits::default());
+    }
+
+    #[test]
+    fn missing_features_and_limits_are_reported() {
+        let config = RendererConfig {
+            required_features: vec![GpuFeature::TimestampQuery, GpuFeature::ShaderF16],
+            webgl2_fallback: false,
+            ..Default::default()
+        };
+        let available = wgpu::Limits { max_bind_groups: 2, ..wgpu::Limits::default() };
+        let (report, _) = check(&config, wgpu::Features::SHADER_F16, &available);
+
+        assert!(!report.is_supported());
+        assert_eq!(report.missing_features, [GpuFeature::TimestampQuery]);
+        assert_eq!(report.failed_limits, [FailedLimit { name: "max_bind_groups", requested: 4, available: 2 }]);
+        assert_eq!(
+            report.to_string(),
+            "adapter `test adapter` lacks feature TimestampQuery, max_bind_groups (requested 4, available 2)"
+        );
+    }
+
+    #[test]
+    fn weak_adapters_fall_back_to_webgl2_limits() {
+        let available = wgpu::Limits { max_texture_dimension_2d: 4096, ..wgpu::Limits::downlevel_webgl2_defaults() };
+        let (report, limits) = check(&RendererConfig::default(), wgpu::Features::empty(), &available);
+        assert!(report.is_supported());
+        assert_eq!(limits.max_storage_buffers_per_shader_stage, 0);
+        // The fallback takes the adapter's texture size rather than WebGL2's.
+        assert_eq!(limits.max_texture_dimension_2d, 4096);
+
+        let config = RendererConfig { webgl2_fallback: false, ..Default::default() };
+        assert!(!check(&config, wgpu::Features::empty(), &available).0.is_supported());
+    }
+
+    #[test]
+    fn unsupported_present_modes_fall_back_to_vsync() {
+        let supported = [wgpu::PresentMode::Fifo, wgpu::PresentMode::Immediate];
+        assert_eq!(PresentMode::Immediate.choose(&supported), wgpu::PresentMode::Immediate);
+        assert_eq!(PresentMode::Mailbox.choose(&supported), wgpu::PresentMode::Fifo);
+        assert_eq!(PresentMode::Vsync.choose(&supported), wgpu::PresentMode::Fifo);
+    }
+}
@@ -1,9 +1,61 @@
-pub struct RenderSystem {
+// rendering.rs
+use crate::engine_core::mesh_pass::add_mesh_pass;
+use crate::engine_core::render_graph::{RenderGraph, ResourceId};
 
+/// The frame's render graph. Add passes through `graph_mut`; a pass that
+/// writes `backbuffer` ends up on screen (or in the offscreen texture). The
+/// graph starts with a `clear` pass on the backbuffer and a `meshes` pass
+/// drawing every visible `RenderableComponent`. Writers of a resource run in
+/// the order they were added, so later passes draw over both.
+pub struct RenderSystem {
+    graph: RenderGraph,
+    backbuffer: ResourceId,
 }
 
 impl RenderSystem {
     pub fn new() -> Self {
-        Self { }
+        Self::with_clear_color(wgpu::Color::BLACK)
+    }
+
+    pub fn with_clear_color(clear_color: wgpu::Color) -> Self {
+        let mut graph = RenderGraph::new();
+        let backbuffer = graph.import_texture("backbuffer");
+        graph.add_pass("clear").write(backbuffer).execute(move |ctx| {
+            ctx.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("clear"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: ctx.texture_view(backbuffer),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(clear_color),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+        });
+        add_mesh_pass(&mut graph, backbuffer);
+        Self { graph, backbuffer }
+    }
+
+    pub fn graph(&self) -> &RenderGraph {
+        &self.graph
+    }
+
+    pub fn graph_mut(&mut self) -> &mut RenderGraph {
+        &mut self.graph
+    }
+
+    /// The texture acquired from `WebGPUResources` for the current frame.
+    pub fn backbuffer(&self) -> ResourceId {
+        self.backbuffer
     }
-}
\ No newline at end of file
+}
+
+impl Default for RenderSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,389 @@
+// replay.rs
+use std::any::TypeId;
+use std::fmt;
+use std::hash::Hasher;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::ecs_core::component::{Component, ComponentManager};
+use crate::ecs_core::schedule::ScheduleError;
+use crate::engine_core::clock::MockClock;
+use crate::engine_core::fixed_time::FixedTime;
+use crate::engine_core::inputhandler::{InputEvent, InputHandler};
+use crate::engine_core::temporal::{AdvancedTime, TimeRadix};
+use crate::engine_core::world::World;
+
+const RECORDING_VERSION: u32 = 1;
+
+/// Everything that entered one frame from outside, plus the state it produced.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FrameRecord {
+    /// What `AdvancedTime` read from its clock; `None` without one.
+    pub clock_ms: Option<f64>,
+    /// The reading started a new time baseline; see `AdvancedTime::resync`.
+    #[serde(default)]
+    pub resync: bool,
+    pub inputs: Vec<InputEvent>,
+    /// `World::state_checksum` after the frame ran.
+    pub checksum: u64,
+}
+
+/// A recorded session. Time and input are the only things that reach the
+/// simulation from outside, so replaying them into a world set up the same way
+/// reproduces every frame exactly.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub version: u32,
+    pub sub_ticks_per_tick: u32,
+    pub ms_per_sub_tick: u32,
+    pub max_steps_per_frame: u32,
+    pub frames: Vec<FrameRecord>,
+}
+
+impl Recording {
+    pub fn to_json(&self) -> Result<String, ReplayError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        Self::check_version(serde_json::from_str(json)?)
+    }
+
+    /// Not available in the browser, which has no file system.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReplayError> {
+        let file = io::BufWriter::new(std::fs::File::create(path)?);
+        Ok(serde_json::to_writer(file, self)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let file = io::BufReader::new(std::fs::File::open(path)?);
+        Self::check_version(serde_json::from_reader(file)?)
+    }
+
+    fn check_version(recording: Recording) -> Result<Self, ReplayError> {
+        if recording.version != RECORDING_VERSION {
+            return Err(ReplayError::Version(recording.version));
+        }
+        Ok(recording)
+    }
+}
+
+/// Collects frames while present in the world; see `World::start_recording`.
+pub struct Recorder {
+    recording: Recording,
+}
+
+impl Recorder {
+    pub(crate) fn push(&mut self, frame: FrameRecord) {
+        self.recording.frames.push(frame);
+    }
+
+    pub fn frames(&self) -> usize {
+        self.recording.frames.len()
+    }
+}
+
+/// Feeds a `Recording` back into a world, frame by frame, and checks that every
+/// frame ends in the recorded state.
+pub struct Replayer {
+    recording: Recording,
+    clock: MockClock,
+    cursor: usize,
+}
+
+impl Replayer {
+    pub fn new(recording: Recording) -> Self {
+        Self { recording, clock: MockClock::new(0.0), cursor: 0 }
+    }
+
+    /// A world with no window or GPU whose time comes from the recording. Add
+    /// the same systems and initial entities as the recorded session before
+    /// replaying into it.
+    pub fn headless_world(&self) -> World {
+        let temporal = AdvancedTime::with_clock(
+            Box::new(self.clock.clone()),
+            self.recording.sub_ticks_per_tick,
+            self.recording.ms_per_sub_tick,
+        );
+        let mut world = World::new();
+        world.insert_time(temporal);
+        if let Some(mut fixed) = world.resource_mut::<FixedTime>() {
+            *fixed = fixed.clone().with_max_steps_per_frame(self.recording.max_steps_per_frame);
+        }
+        world.insert_resource(InputHandler::new());
+        world
+    }
+
+    /// Runs the next recorded frame. `None` once the recording is exhausted.
+    pub fn step(&mut self, world: &mut World) -> Option<Result<(), ReplayError>> {
+        let frame = self.recording.frames.get(self.cursor)?;
+        let index = self.cursor;
+        self.cursor += 1;
+
+        if let Some(clock_ms) = frame.clock_ms {
+            self.clock.set(clock_ms);
+        }
+        if frame.resync {
+            if let Some(mut time) = world.resources.get_non_send_mut::<AdvancedTime>() {
+                time.resync();
+            }
+        }
+        if let Some(mut input) = world.resource_mut::<InputHandler>() {
+            for event in &frame.inputs {
+                input.push(event.clone());
+            }
+        }
+        if let Err(error) = world.run_frame() {
+            return Some(Err(ReplayError::Schedule(error)));
+        }
+
+        let actual = world.state_checksum();
+        if actual != frame.checksum {
+            return Some(Err(ReplayError::Diverged { frame: index, expected: frame.checksum, actual }));
+        }
+        Some(Ok(()))
+    }
+
+    /// Replays every remaining frame, stopping at the first divergence. Returns
+    /// the number of frames run.
+    pub fn run(&mut self, world: &mut World) -> Result<usize, ReplayError> {
+        let start = self.cursor;
+        while let Some(result) = self.step(world) {
+            result?;
+        }
+        Ok(self.cursor - start)
+    }
+
+    /// Index of the next frame to replay.
+    pub fn position(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn len(&self) -> usize {
+        self.recording.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.recording.frames.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// Written by an incompatible version of the engine.
+    Version(u32),
+    Schedule(ScheduleError),
+    /// The world ended `frame` in a different state than when it was recorded.
+    Diverged { frame: usize, expected: u64, actual: u64 },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access recording: {}", error),
+            ReplayError::Format(error) => write!(f, "malformed recording: {}", error),
+            ReplayError::Version(version) => {
+                write!(f, "recording version {} is not supported (expected {})", version, RECORDING_VERSION)
+            }
+            ReplayError::Schedule(error) => write!(f, "replayed frame failed to run: {}", error),
+            ReplayError::Diverged { frame, expected, actual } => write!(
+                f,
+                "replay diverged at frame {}: checksum {:016x}, recorded {:016x}",
+                frame, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for ReplayError {
+    fn from(error: serde_json::Error) -> Self {
+        ReplayError::Format(error)
+    }
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` its output is fixed across Rust
+/// versions and platforms, so checksums in a saved recording stay comparable.
+pub struct StateHasher(u64);
+
+impl StateHasher {
+    pub fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StateHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl io::Write for StateHasher {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        Hasher::write(self, bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feeds one component type's storage into the checksum.
+pub(crate) type ChecksumFn = fn(&ComponentManager, &mut StateHasher);
+
+fn hash_storage<C: Component + Serialize>(components: &ComponentManager, hasher: &mut StateHasher) {
+    hasher.write(std::any::type_name::<C>().as_bytes());
+    let Some(storage) = components.storage::<C>() else { return };
+    // Fixed-width little-endian, so native and wasm builds agree.
+    hasher.write(&(storage.len() as u64).to_le_bytes());
+    for (entity, component) in storage.iter() {
+        hasher.write(&entity.index().to_le_bytes());
+        hasher.write(&entity.generation().to_le_bytes());
+        serde_json::to_writer(&mut *hasher, component).expect("component failed to serialize for checksum");
+    }
+}
+
+impl World {
+    /// Includes every `C` in `state_checksum`. Register the components that
+    /// make up the simulation; render-only ones can be left out.
+    pub fn checksum_component<C: Component + Serialize>(&mut self) {
+        let type_id = TypeId::of::<C>();
+        if !self.checksummed.iter().any(|(registered, _)| *registered == type_id) {
+            self.checksummed.push((type_id, hash_storage::<C>));
+        }
+    }
+
+    /// Hash of every component registered with `checksum_component`, with the
+    /// entity each belongs to. Storage order is part of the hash; it only
+    /// differs between runs if the runs themselves did.
+    pub fn state_checksum(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        for (_, hash) in &self.checksummed {
+            hash(&self.components, &mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Starts recording every frame run with `run_frame`. For a faithful replay
+    /// start before the first frame, once the world is set up.
+    pub fn start_recording(&mut self) {
+        let radix = self.resources.get_non_send::<AdvancedTime>().map_or(TimeRadix::default(), |time| time.radix());
+        let max_steps_per_frame = self.resource::<FixedTime>().map_or(5, |fixed| fixed.max_steps_per_frame());
+        self.insert_resource(Recorder {
+            recording: Recording {
+                version: RECORDING_VERSION,
+                sub_ticks_per_tick: radix.sub_ticks_per_tick(),
+                ms_per_sub_tick: radix.ms_per_sub_tick(),
+                max_steps_per_frame,
+                frames: Vec::new(),
+            },
+        });
+    }
+
+    /// Stops recording and returns what was recorded.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        self.resources.remove::<Recorder>().map(|recorder| recorder.recording)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::transform_component::Transform;
+    use crate::ecs_core::entity::Entity;
+    use crate::ecs_core::event::EventReader;
+    use crate::ecs_core::schedule::Stage;
+    use crate::ecs_core::system::System;
+    use crate::LuminaEngine;
+
+    /// Each fixed step moves every `Transform` up by one, and right by one per
+    /// key pressed, so both time and input end up in the checksum.
+    struct Walk {
+        reader: EventReader<InputEvent>,
+    }
+
+    impl System for Walk {
+        fn update(&mut self, world: &mut World) {
+            let events = world.events::<InputEvent>();
+            let presses = self.reader.read(&events).filter(|event| matches!(event, InputEvent::KeyDown { .. })).count();
+            for (_, mut transform) in world.query::<&mut Transform>().iter() {
+                transform.translation.x += presses as f32;
+                transform.translation.y += 1.0;
+            }
+        }
+    }
+
+    fn session(world: &mut World) -> Entity {
+        world.schedule.add_system(Stage::FixedUpdate, Walk { reader: EventReader::new() });
+        world.spawn((Transform::IDENTITY,))
+    }
+
+    fn record() -> (Recording, Transform) {
+        let clock = MockClock::new(0.0);
+        let mut world = World::new();
+        world.insert_time(AdvancedTime::with_clock(Box::new(clock.clone()), 10, 10));
+        world.insert_resource(InputHandler::new());
+        let entity = session(&mut world);
+
+        world.start_recording();
+        for frame in 0..20 {
+            // One long stall, to hit the catch-up cap.
+            clock.advance(if frame == 10 { 900.0 } else { 33.0 });
+            if frame % 3 == 0 {
+                world.resource_mut::<InputHandler>().unwrap().push(InputEvent::KeyDown { code: "KeyD".into() });
+            }
+            world.run_frame().unwrap();
+        }
+        let recording = world.stop_recording().unwrap();
+        let transform = *world.components.get::<Transform>(&entity).unwrap();
+        (recording, transform)
+    }
+
+    #[test]
+    fn recorded_session_replays_headless() {
+        let (recording, recorded) = record();
+        assert!(recorded.translation.x > 0.0 && recorded.translation.y > 0.0);
+        let recording = Recording::from_json(&recording.to_json().unwrap()).unwrap();
+
+        let mut replayer = Replayer::new(recording);
+        let mut engine = LuminaEngine::replaying(&replayer);
+        let entity = session(engine.world_mut());
+        assert_eq!(replayer.run(engine.world_mut()).unwrap(), 20);
+        assert_eq!(*engine.world().components.get::<Transform>(&entity).unwrap(), recorded);
+    }
+
+    #[test]
+    fn replay_reports_the_first_diverging_frame() {
+        let (mut recording, _) = record();
+        recording.frames[5].checksum ^= 1;
+
+        let mut replayer = Replayer::new(recording);
+        let mut world = replayer.headless_world();
+        session(&mut world);
+        assert!(matches!(replayer.run(&mut world), Err(ReplayError::Diverged { frame: 5, .. })));
+        assert_eq!(replayer.position(), 6);
+    }
+}
@@ -0,0 +1,245 @@
+// scene_graph.rs
+// Parent/child hierarchy on top of the ECS. The links live in `Parent` and
+// `Children` components and are kept in sync by the `World` methods below;
+// editing those components directly can leave the two sides disagreeing.
+use std::fmt;
+use glam::Mat4;
+use crate::components::transform_component::Transform;
+use crate::ecs_core::command::Commands;
+use crate::ecs_core::entity::Entity;
+use crate::engine_core::world::World;
+
+/// The entity this one is attached to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Parent(Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+/// Entities attached to this one, in the order they were attached.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Children(Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn as_slice(&self) -> &[Entity] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HierarchyError {
+    /// The handle is stale.
+    NotAlive(Entity),
+    /// Attaching would make the entity its own ancestor.
+    Cycle { child: Entity, parent: Entity },
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HierarchyError::NotAlive(entity) => write!(f, "{:?} is not alive", entity),
+            HierarchyError::Cycle { child, parent } => {
+                write!(f, "cannot attach {:?} to {:?}: it is an ancestor of {:?}", child, parent, parent)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+impl World {
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.components.get::<Parent>(&entity).map(|parent| parent.get())
+    }
+
+    /// Attaches `child` to `parent`, detaching it from any previous parent. The
+    /// child's `Transform` is kept as is, so it moves with its new parent.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), HierarchyError> {
+        for entity in [child, parent] {
+            if !self.entities.is_alive(entity) {
+                return Err(HierarchyError::NotAlive(entity));
+            }
+        }
+        let mut ancestor = Some(parent);
+        while let Some(current) = ancestor {
+            if current == child {
+                return Err(HierarchyError::Cycle { child, parent });
+            }
+            ancestor = self.parent(current);
+        }
+
+        self.detach(child);
+        self.components.insert(child, Parent(parent));
+        match self.components.get_mut::<Children>(&parent) {
+            Some(children) => children.0.push(child),
+            None => {
+                self.components.insert(parent, Children(vec![child]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Detaches `child` from its parent, keeping its `Transform` as is, which
+    /// now counts from the world origin.
+    pub fn remove_parent(&mut self, child: Entity) {
+        self.detach(child);
+        self.components.remove::<Parent>(&child);
+    }
+
+    /// Moves `child` under `parent` (or to the root with `None`) and rewrites
+    /// its `Transform` so that it stays where it is in world space.
+    pub fn reparent(&mut self, child: Entity, parent: Option<Entity>) -> Result<(), HierarchyError> {
+        for entity in std::iter::once(child).chain(parent) {
+            if !self.entities.is_alive(entity) {
+                return Err(HierarchyError::NotAlive(entity));
+            }
+        }
+        let global = self.global_matrix(child);
+        let local = match parent {
+            Some(parent) => {
+                let parent_global = self.global_matrix(parent);
+                self.set_parent(child, parent)?;
+                parent_global.inverse() * global
+            }
+            None => {
+                self.remove_parent(child);
+                global
+            }
+        };
+        self.components.insert(child, Transform::from_matrix(local));
+        Ok(())
+    }
+
+    /// World-space matrix of `entity` computed from the `Transform`s up its
+    /// ancestor chain, so it is current even before propagation has run.
+    /// Entities without a `Transform` count as the identity.
+    pub fn global_matrix(&self, entity: Entity) -> Mat4 {
+        let local = |entity: Entity| {
+            self.components.get::<Transform>(&entity).map_or(Mat4::IDENTITY, |transform| transform.compute_matrix())
+        };
+        let mut matrix = local(entity);
+        let mut ancestor = self.parent(entity);
+        while let Some(current) = ancestor {
+            matrix = local(current) * matrix;
+            ancestor = self.parent(current);
+        }
+        matrix
+    }
+
+    /// Despawns `entity` and all of its descendants, and removes it from its
+    /// parent's `Children`. Returns `false` if the handle was already stale.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> bool {
+        if !self.entities.is_alive(entity) {
+            return false;
+        }
+        let mut pending = vec![entity];
+        while let Some(current) = pending.pop() {
+            if let Some(children) = self.components.get::<Children>(&current) {
+                pending.extend(children.iter());
+            }
+            self.despawn(current);
+        }
+        true
+    }
+
+    /// Removes `child` from its current parent's `Children`, leaving its own
+    /// `Parent` in place for the caller to overwrite or remove.
+    pub(crate) fn detach(&mut self, child: Entity) {
+        let Some(parent) = self.parent(child) else { return };
+        let now_empty = self.components.get_mut::<Children>(&parent).is_some_and(|children| {
+            children.0.retain(|&sibling| sibling != child);
+            children.0.is_empty()
+        });
+        if now_empty {
+            self.components.remove::<Children>(&parent);
+        }
+    }
+}
+
+impl Commands<'_> {
+    /// Deferred `World::despawn_recursive`.
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |world| {
+            world.despawn_recursive(entity);
+        });
+    }
+
+    /// Deferred `World::reparent`. Failures are logged and otherwise ignored.
+    pub fn reparent(&mut self, child: Entity, parent: Option<Entity>) {
+        self.add(move |world| {
+            if let Err(error) = world.reparent(child, parent) {
+                tracing::warn!("reparent failed: {}", error);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+    use super::*;
+    use crate::components::transform_component::GlobalTransform;
+    use crate::ecs_core::system::System;
+    use crate::systems::transform_system::TransformPropagationSystem;
+
+    #[test]
+    fn reparent_rejects_dead_entities() {
+        let mut world = World::new();
+        let child = world.spawn((Transform::IDENTITY,));
+        let parent = world.spawn((Transform::IDENTITY,));
+        world.despawn(parent);
+
+        assert_eq!(world.reparent(child, Some(parent)), Err(HierarchyError::NotAlive(parent)));
+        assert_eq!(world.parent(child), None);
+
+        world.despawn(child);
+        assert_eq!(world.reparent(child, None), Err(HierarchyError::NotAlive(child)));
+    }
+
+    #[test]
+    fn despawn_prunes_both_sides_of_the_hierarchy() {
+        let mut world = World::new();
+        let root = world.spawn((Transform::IDENTITY,));
+        let middle = world.spawn((Transform::IDENTITY,));
+        let leaf = world.spawn((Transform::IDENTITY,));
+        let sibling = world.spawn((Transform::IDENTITY,));
+        world.set_parent(middle, root).unwrap();
+        world.set_parent(sibling, root).unwrap();
+        world.set_parent(leaf, middle).unwrap();
+
+        world.despawn(middle);
+        assert_eq!(world.components.get::<Children>(&root).unwrap().as_slice(), &[sibling]);
+        assert_eq!(world.parent(leaf), None);
+
+        world.despawn(sibling);
+        assert!(world.components.get::<Children>(&root).is_none());
+    }
+
+    #[test]
+    fn parent_without_transform_counts_as_identity() {
+        let mut world = World::new();
+        let group = world.spawn((Transform::IDENTITY,));
+        world.components.remove::<Transform>(&group);
+        let child = world.spawn((Transform::from_translation(Vec3::X),));
+        world.set_parent(child, group).unwrap();
+
+        TransformPropagationSystem::new().update(&mut world);
+        let global = world.components.get::<GlobalTransform>(&child).unwrap();
+        assert_eq!(global.translation(), Vec3::X);
+    }
+}
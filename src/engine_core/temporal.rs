@@ -1,44 +1,281 @@
 // temporal.rs
 
-use std::collections::BinaryHeap; //for event que
+use std::collections::{BinaryHeap, HashMap}; //for event que
 use std::cmp::Ordering;
-use web_sys::Performance;
-use tracing::{info, error};
+use std::ops::{Add, AddAssign, Sub, SubAssign};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::Duration;
+use crate::ecs_core::event::Event;
+use crate::engine_core::clock::{self, Clock};
+use crate::engine_core::world::World;
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+/// Units of a `MixedRadixTime`: how many sub-ticks make a tick and how many
+/// milliseconds make a sub-tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimeRadix {
+    sub_ticks_per_tick: u32,
+    ms_per_sub_tick: u32,
+}
+
+impl TimeRadix {
+    /// Zero is treated as one.
+    pub fn new(sub_ticks_per_tick: u32, ms_per_sub_tick: u32) -> Self {
+        Self {
+            sub_ticks_per_tick: sub_ticks_per_tick.max(1),
+            ms_per_sub_tick: ms_per_sub_tick.max(1),
+        }
+    }
+
+    pub fn sub_ticks_per_tick(&self) -> u32 {
+        self.sub_ticks_per_tick
+    }
+
+    pub fn ms_per_sub_tick(&self) -> u32 {
+        self.ms_per_sub_tick
+    }
+
+    pub fn ms_per_tick(&self) -> u64 {
+        self.sub_ticks_per_tick as u64 * self.ms_per_sub_tick as u64
+    }
+}
+
+impl Default for TimeRadix {
+    fn default() -> Self {
+        Self::new(10, 10)
+    }
+}
+
+/// A span or point of game time, split into ticks, sub-ticks and leftover
+/// milliseconds, alongside a count of frames. Values are kept normalised, so
+/// each unit stays below its radix.
+///
+/// Time and frames are independent axes: arithmetic works on both, ordering
+/// compares elapsed time first and frames only to break ties, and a timer delay
+/// can use either or both.
+#[derive(Clone, Copy, Debug)]
 pub struct MixedRadixTime {
     frame_count: u64,
     ticks: u64,
     sub_ticks: u32,
     milliseconds: u32,
+    radix: TimeRadix,
 }
 
 impl MixedRadixTime {
+    /// Zero, in the default radix.
     pub fn new() -> Self {
-        Self { frame_count: 0, ticks: 0, sub_ticks: 0, milliseconds: 0 }
+        Self::zero(TimeRadix::default())
+    }
+
+    pub fn zero(radix: TimeRadix) -> Self {
+        Self { frame_count: 0, ticks: 0, sub_ticks: 0, milliseconds: 0, radix }
+    }
+
+    /// Carries overflowing units, e.g. 25 sub-ticks of a 10-sub-tick radix
+    /// become 2 ticks and 5 sub-ticks.
+    pub fn from_parts(frame_count: u64, ticks: u64, sub_ticks: u32, milliseconds: u32, radix: TimeRadix) -> Self {
+        let mut time = Self { frame_count, ticks, sub_ticks, milliseconds, radix };
+        time.normalize();
+        time
+    }
+
+    /// A delay counted in frames only.
+    pub fn from_frames(frame_count: u64) -> Self {
+        Self { frame_count, ..Self::new() }
+    }
+
+    pub fn from_ms(ms: u64, radix: TimeRadix) -> Self {
+        let ms_per_tick = radix.ms_per_tick();
+        let rest = ms % ms_per_tick;
+        Self {
+            frame_count: 0,
+            ticks: ms / ms_per_tick,
+            sub_ticks: (rest / radix.ms_per_sub_tick as u64) as u32,
+            milliseconds: (rest % radix.ms_per_sub_tick as u64) as u32,
+            radix,
+        }
+    }
+
+    /// Truncates to whole milliseconds.
+    pub fn from_duration(duration: Duration, radix: TimeRadix) -> Self {
+        Self::from_ms(duration.as_millis() as u64, radix)
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    pub fn sub_ticks(&self) -> u32 {
+        self.sub_ticks
+    }
+
+    pub fn milliseconds(&self) -> u32 {
+        self.milliseconds
+    }
+
+    pub fn radix(&self) -> TimeRadix {
+        self.radix
+    }
+
+    /// Total time in milliseconds; frames are not included.
+    pub fn as_ms(&self) -> u64 {
+        self.ticks * self.radix.ms_per_tick()
+            + self.sub_ticks as u64 * self.radix.ms_per_sub_tick as u64
+            + self.milliseconds as u64
+    }
+
+    pub fn as_duration(&self) -> Duration {
+        Duration::from_millis(self.as_ms())
+    }
+
+    /// The same time and frame count expressed in another radix.
+    pub fn with_radix(&self, radix: TimeRadix) -> Self {
+        Self { frame_count: self.frame_count, ..Self::from_ms(self.as_ms(), radix) }
+    }
+
+    /// `None` if `other` is later on either axis.
+    pub fn checked_sub(&self, other: &MixedRadixTime) -> Option<Self> {
+        let ms = self.as_ms().checked_sub(other.as_ms())?;
+        let frame_count = self.frame_count.checked_sub(other.frame_count)?;
+        Some(Self { frame_count, ..Self::from_ms(ms, self.radix) })
     }
 
-    pub fn advance(&mut self, delta_ms: u32, sub_ticks_per_tick: u32, ms_per_sub_tick: u32) {
+    /// Adds a frame and `delta_ms` of time.
+    pub fn advance(&mut self, delta_ms: u32) {
         self.frame_count += 1;
         self.milliseconds += delta_ms;
-        let new_sub_ticks = self.milliseconds / ms_per_sub_tick;
-        self.milliseconds %= ms_per_sub_tick;
-        self.sub_ticks += new_sub_ticks;
-        let new_ticks = self.sub_ticks / sub_ticks_per_tick;
-        self.sub_ticks %= sub_ticks_per_tick;
-        self.ticks += new_ticks as u64;
+        self.normalize();
+    }
+
+    fn normalize(&mut self) {
+        let radix = self.radix;
+        let carried_sub_ticks = self.milliseconds / radix.ms_per_sub_tick;
+        self.milliseconds %= radix.ms_per_sub_tick;
+        let sub_ticks = self.sub_ticks as u64 + carried_sub_ticks as u64;
+        self.ticks += sub_ticks / radix.sub_ticks_per_tick as u64;
+        self.sub_ticks = (sub_ticks % radix.sub_ticks_per_tick as u64) as u32;
+    }
+}
+
+impl Default for MixedRadixTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The result takes the radix of the left operand.
+impl Add for MixedRadixTime {
+    type Output = MixedRadixTime;
+
+    fn add(self, other: MixedRadixTime) -> MixedRadixTime {
+        MixedRadixTime {
+            frame_count: self.frame_count + other.frame_count,
+            ..MixedRadixTime::from_ms(self.as_ms() + other.as_ms(), self.radix)
+        }
+    }
+}
+
+impl AddAssign for MixedRadixTime {
+    fn add_assign(&mut self, other: MixedRadixTime) {
+        *self = *self + other;
+    }
+}
+
+/// Saturates at zero on each axis; see `checked_sub` to detect that.
+impl Sub for MixedRadixTime {
+    type Output = MixedRadixTime;
+
+    fn sub(self, other: MixedRadixTime) -> MixedRadixTime {
+        MixedRadixTime {
+            frame_count: self.frame_count.saturating_sub(other.frame_count),
+            ..MixedRadixTime::from_ms(self.as_ms().saturating_sub(other.as_ms()), self.radix)
+        }
+    }
+}
+
+impl SubAssign for MixedRadixTime {
+    fn sub_assign(&mut self, other: MixedRadixTime) {
+        *self = *self - other;
+    }
+}
+
+impl PartialEq for MixedRadixTime {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for MixedRadixTime {}
+
+impl PartialOrd for MixedRadixTime {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
+impl Ord for MixedRadixTime {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_ms()
+            .cmp(&other.as_ms())
+            .then_with(|| self.frame_count.cmp(&other.frame_count))
+    }
+}
+
+impl From<Duration> for MixedRadixTime {
+    /// In the default radix.
+    fn from(duration: Duration) -> Self {
+        MixedRadixTime::from_duration(duration, TimeRadix::default())
+    }
+}
+
+pub type TimerId = u64;
+
+type TimerPayload = Box<dyn FnMut(&mut World)>;
+
+/// A point on the timer timeline. A timer is due once both the game time in
+/// milliseconds and the frame count have reached its deadline, so a delay can
+/// be given in time, in frames, or both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Deadline {
+    ms: u64,
+    frame: u64,
+}
+
+impl Deadline {
+    fn after(self, delay: Deadline) -> Deadline {
+        Deadline { ms: self.ms + delay.ms, frame: self.frame + delay.frame }
+    }
+
+    fn until(self, later: Deadline) -> Deadline {
+        Deadline { ms: later.ms.saturating_sub(self.ms), frame: later.frame.saturating_sub(self.frame) }
+    }
+}
+
+struct Timer {
+    due: Deadline,
+    /// `Some` for repeating timers.
+    interval: Option<Deadline>,
+    /// Time left until due while the timer is paused.
+    paused: Option<Deadline>,
+    /// Taken out while the payload runs.
+    payload: Option<TimerPayload>,
+}
+
+/// Heap entry for a timer. Entries are never removed in place: one whose timer
+/// was cancelled, paused or rescheduled no longer matches `Timer::due` and is
+/// skipped when it reaches the top.
 struct TemporalEvent {
-    time: MixedRadixTime,
-    id: u64,
-    payload: Box<dyn Fn()>,
+    due: Deadline,
+    id: TimerId,
 }
 
 impl PartialEq for TemporalEvent { 
     fn eq(&self, other: &Self) -> bool {
-        self.time == other.time && self.id == other.id
+        self.due == other.due && self.id == other.id
     }
 }
 
@@ -53,129 +290,917 @@ impl PartialOrd for TemporalEvent {
 impl Ord for TemporalEvent {
     fn cmp(&self, other: &Self) -> Ordering {
         // (min-heap behavior)
-        other.time.cmp(&self.time)
+        other.due.cmp(&self.due)
             .then_with(|| other.id.cmp(&self.id))
     }
 }
 
-pub struct AdvancedTime {
+/// Name of a time domain. `GAME`, `UI` and `REAL` always exist; more can be
+/// added with `AdvancedTime::add_domain`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DomainId(pub &'static str);
+
+impl DomainId {
+    /// Gameplay time. The default for everything on `AdvancedTime` itself and
+    /// the domain that drives `FixedUpdate`.
+    pub const GAME: DomainId = DomainId("game");
+    /// Menus and HUD animation; keeps running while `GAME` is paused.
+    pub const UI: DomainId = DomainId("ui");
+    /// Unscaled wall-clock time. Nothing in the engine pauses or scales it.
+    pub const REAL: DomainId = DomainId("real");
+}
+
+static NEXT_TIMER_ID: AtomicU64 = AtomicU64::new(0);
+
+/// One independent timeline: its own scale, pause state, elapsed time and
+/// timers, all advanced from the shared clock by `AdvancedTime::update`.
+pub struct TimeDomain {
     mixed_time: MixedRadixTime,
     time_scale: f32,
     paused: bool,
-    performance: Performance,
-    last_timestamp: f64,
     event_queue: BinaryHeap<TemporalEvent>,
-    next_event_id: u64,
-    sub_ticks_per_tick: u32,
-    ms_per_sub_tick: u32,
+    timers: HashMap<TimerId, Timer>,
+    /// Timers that came due in the last update, in firing order, waiting for
+    /// `run_due_timers`.
+    due_timers: Vec<TimerId>,
     last_delta_ms: u32,
+    consumed_ticks: u64,
+    /// Scaled time not yet added to `mixed_time`, always below 1 ms. Carried
+    /// over so truncating to whole milliseconds never loses time.
+    carry_ms: f64,
+    delta_ms: f64,
 }
 
-impl AdvancedTime {
-    pub fn new(sub_ticks_per_tick: u32, ms_per_sub_tick: u32) -> Self {
-        let performance = web_sys::window()
-            .expect("no global accessible window exists")
-            .performance()
-            .expect("window should have performance");
-
+impl TimeDomain {
+    fn new(radix: TimeRadix) -> Self {
         Self {
-            mixed_time: MixedRadixTime::new(),
+            mixed_time: MixedRadixTime::zero(radix),
             time_scale: 1.0,
             paused: false,
-            performance,
-            last_timestamp: 0.0,
             event_queue: BinaryHeap::new(),
-            next_event_id: 0,
-            sub_ticks_per_tick,
-            ms_per_sub_tick,
+            timers: HashMap::new(),
+            due_timers: Vec::new(),
             last_delta_ms: 0,
-        } 
+            consumed_ticks: 0,
+            carry_ms: 0.0,
+            delta_ms: 0.0,
+        }
     }
 
-    pub fn update(&mut self) {
+    /// Advances by `unscaled_ms` of clock time times this domain's scale, then
+    /// collects due timers. Does nothing while paused.
+    fn advance(&mut self, unscaled_ms: f64) {
         if self.paused {
             return;
         }
-
-        let current_timestamp = self.performance.now();
-        if self.last_timestamp == 0.0 {
-            self.last_timestamp = current_timestamp;
-            return;
-        }
-
-        let delta_ms = ((current_timestamp - self.last_timestamp) * self.time_scale as f64) as u32;
-        self.last_timestamp = current_timestamp;
-        self.last_delta_ms = delta_ms;
-        self.mixed_time.advance(delta_ms, self.sub_ticks_per_tick, self.ms_per_sub_tick);
+        self.delta_ms = unscaled_ms * self.time_scale as f64;
+        let total_ms = self.delta_ms + self.carry_ms;
+        let whole_ms = total_ms.floor();
+        self.carry_ms = total_ms - whole_ms;
+        self.last_delta_ms = whole_ms as u32;
+        self.mixed_time.advance(self.last_delta_ms);
 
         self.process_events();
     }
 
-    pub fn schedule_event<F>(&mut self, delay: MixedRadixTime, payload: F) -> u64
+    /// Runs `payload` once, `delay` from now. Payloads run with the world from
+    /// `TimerSystem` at the start of the frame they come due in.
+    pub fn schedule_event<F>(&mut self, delay: MixedRadixTime, payload: F) -> TimerId
     where
-        F: Fn() + 'static,
+        F: FnMut(&mut World) + 'static,
     {
-        let event_time = MixedRadixTime {
-            frame_count: self.mixed_time.frame_count + delay.frame_count,
-            ticks: self.mixed_time.ticks + delay.ticks,
-            sub_ticks: self.mixed_time.sub_ticks + delay.sub_ticks,
-            milliseconds: self.mixed_time.milliseconds + delay.milliseconds,
-        };
-    
-        let id = self.next_event_id;
-        self.next_event_id += 1;
-    
-        self.event_queue.push(TemporalEvent {
-            time: event_time,
-            id,
-            payload: Box::new(payload),
-        });
-    
-        id    
+        let due = self.now().after(to_deadline(&delay));
+        self.push_timer(due, None, Box::new(payload))
+    }
+
+    /// Runs `payload` every `interval`, starting one interval from now, until
+    /// cancelled. Deadlines advance by exactly one interval per run, keeping
+    /// the timer in phase; a timer that fell more than an interval behind (a
+    /// stall, a long frame) runs once and is rescheduled one interval from
+    /// now instead of catching up on every missed run. A zero interval means
+    /// every frame.
+    pub fn schedule_repeating<F>(&mut self, interval: MixedRadixTime, payload: F) -> TimerId
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        let mut interval = to_deadline(&interval);
+        if interval.ms == 0 && interval.frame == 0 {
+            interval.frame = 1;
+        }
+        let due = self.now().after(interval);
+        self.push_timer(due, Some(interval), Box::new(payload))
+    }
+
+    /// Sends `event` once, `delay` from now. `E` must have been added with
+    /// `World::add_event`.
+    pub fn schedule_send<E: Event>(&mut self, delay: MixedRadixTime, event: E) -> TimerId {
+        let mut event = Some(event);
+        self.schedule_event(delay, move |world| {
+            if let Some(event) = event.take() {
+                world.send_event(event);
+            }
+        })
     }
 
+    /// Sends a copy of `event` every `interval`.
+    pub fn schedule_send_repeating<E: Event + Clone>(&mut self, interval: MixedRadixTime, event: E) -> TimerId {
+        self.schedule_repeating(interval, move |world| world.send_event(event.clone()))
+    }
+
+    /// Stops the timer for good. Returns `false` if it already finished or was
+    /// cancelled. A timer may cancel itself from its own payload.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        self.timers.remove(&id).is_some()
+    }
+
+    /// Freezes the timer; the time it had left is kept for `resume_timer`.
+    pub fn pause_timer(&mut self, id: TimerId) -> bool {
+        let now = self.now();
+        match self.timers.get_mut(&id) {
+            Some(timer) if timer.paused.is_none() => {
+                timer.paused = Some(now.until(timer.due));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn resume_timer(&mut self, id: TimerId) -> bool {
+        let now = self.now();
+        let Some(timer) = self.timers.get_mut(&id) else { return false };
+        let Some(remaining) = timer.paused.take() else { return false };
+        timer.due = now.after(remaining);
+        self.event_queue.push(TemporalEvent { due: timer.due, id });
+        true
+    }
+
+    /// Whether the timer is still pending (including paused ones).
+    pub fn is_scheduled(&self, id: TimerId) -> bool {
+        self.timers.contains_key(&id)
+    }
+
+    pub fn is_timer_paused(&self, id: TimerId) -> bool {
+        self.timers.get(&id).is_some_and(|timer| timer.paused.is_some())
+    }
+
+    fn push_timer(&mut self, due: Deadline, interval: Option<Deadline>, payload: TimerPayload) -> TimerId {
+        let id = NEXT_TIMER_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        self.timers.insert(id, Timer { due, interval, paused: None, payload: Some(payload) });
+        self.event_queue.push(TemporalEvent { due, id });
+        id
+    }
+
+    /// Time elapsed in this domain so far, in milliseconds.
+    pub fn elapsed_ms(&self) -> u64 {
+        self.mixed_time.as_ms()
+    }
+
+    /// Elapsed time and frame count of this domain.
+    pub fn now_time(&self) -> MixedRadixTime {
+        self.mixed_time
+    }
+
+    pub fn radix(&self) -> TimeRadix {
+        self.mixed_time.radix
+    }
+
+    fn now(&self) -> Deadline {
+        Deadline { ms: self.elapsed_ms(), frame: self.mixed_time.frame_count }
+    }
+
+    /// Whole milliseconds the mixed-radix clock advanced by last frame. With
+    /// the fractional carry these add up to `elapsed` over time, but a single
+    /// value can be off by up to a millisecond; prefer `delta_seconds`.
     pub fn get_delta_time(&self) -> u32 {
         self.last_delta_ms
     }
 
-fn process_events(&mut self) {
-    while let Some(event) = self.event_queue.peek() {
-        if event.time.frame_count > self.mixed_time.frame_count {
-            break;
-        }
-        if event.time.frame_count == self.mixed_time.frame_count
-            && (event.time.ticks > self.mixed_time.ticks
-                || (event.time.ticks == self.mixed_time.ticks
-                    && (event.time.sub_ticks > self.mixed_time.sub_ticks
-                        || (event.time.sub_ticks == self.mixed_time.sub_ticks
-                            && event.time.milliseconds > self.mixed_time.milliseconds))))
-        {
-            break;
+    /// Scaled time since the previous frame, at full precision. Zero while paused.
+    pub fn delta(&self) -> Duration {
+        Duration::from_secs_f64(self.delta_ms / 1000.0)
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        (self.delta_ms / 1000.0) as f32
+    }
+
+    /// Scaled time since the first update. Built from the integer mixed-radix
+    /// clock plus the sub-millisecond carry, so it does not drift.
+    pub fn elapsed(&self) -> Duration {
+        self.mixed_time.as_duration() + Duration::from_secs_f64(self.carry_ms / 1000.0)
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed().as_secs_f64()
+    }
+
+    /// Length of one tick in milliseconds.
+    pub fn tick_ms(&self) -> u32 {
+        self.radix().ms_per_tick() as u32
+    }
+
+    /// Whole ticks that elapsed since the last call; feeds `FixedTime`.
+    pub fn take_ticks(&mut self) -> u64 {
+        let elapsed = self.mixed_time.ticks - self.consumed_ticks;
+        self.consumed_ticks = self.mixed_time.ticks;
+        elapsed
+    }
+
+    /// Moves every timer that is due into `due_timers`, rescheduling repeating
+    /// ones.
+    fn process_events(&mut self) {
+        let now = self.now();
+        let mut waiting_on_frames = Vec::new();
+        while let Some(event) = self.event_queue.peek() {
+            if event.due.ms > now.ms {
+                break;
+            }
+            let event = self.event_queue.pop().unwrap();
+            let Some(timer) = self.timers.get_mut(&event.id) else { continue };
+            if timer.paused.is_some() || timer.due != event.due {
+                continue;
+            }
+            if event.due.frame > now.frame {
+                waiting_on_frames.push(event);
+                continue;
+            }
+
+            self.due_timers.push(event.id);
+            if let Some(interval) = timer.interval {
+                let mut next = timer.due.after(interval);
+                if next.ms <= now.ms && next.frame <= now.frame {
+                    next = now.after(interval);
+                }
+                timer.due = next;
+                self.event_queue.push(TemporalEvent { due: timer.due, id: event.id });
+            }
         }
-        
-        let event = self.event_queue.pop().unwrap();
-        (event.payload)();
+        self.event_queue.extend(waiting_on_frames);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
     }
-}
 
     pub fn set_time_scale(&mut self, scale: f32) {
         self.time_scale = scale.max(0.0);
     }
 
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Freezes the domain's time and timers; other domains keep running.
     pub fn pause(&mut self) {
         self.paused = true;
+        self.clear_delta();
+    }
+
+    fn clear_delta(&mut self) {
+        self.delta_ms = 0.0;
+        self.last_delta_ms = 0;
     }
 
     pub fn resume(&mut self) {
         self.paused = false;
-        self.last_timestamp = self.performance.now();
     }
 
     pub fn get_interpolation_factor(&self) -> f32 {
-        let sub_ticks_fraction = self.mixed_time.sub_ticks as f32 / self.sub_ticks_per_tick as f32;
-        let milliseconds_fraction = self.mixed_time.milliseconds as f32 / (self.sub_ticks_per_tick * self.ms_per_sub_tick) as f32;
+        let radix = self.radix();
+        let sub_ticks_fraction = self.mixed_time.sub_ticks as f32 / radix.sub_ticks_per_tick as f32;
+        let milliseconds_fraction = (self.mixed_time.milliseconds as f64 + self.carry_ms) as f32 / radix.ms_per_tick() as f32;
         
         sub_ticks_fraction + milliseconds_fraction
     }
+
+    fn snapshot(&self) -> TimeSnapshot {
+        TimeSnapshot {
+            delta: self.delta(),
+            elapsed: self.elapsed(),
+            time_scale: self.time_scale,
+            paused: self.paused,
+            frame_count: self.mixed_time.frame_count,
+        }
+    }
+}
+
+fn to_deadline(time: &MixedRadixTime) -> Deadline {
+    Deadline { ms: time.as_ms(), frame: time.frame_count }
+}
+
+/// The clock and every time domain. Lives in the world as a non-send resource
+/// (the browser clock is a JS handle); systems that only need readings use the
+/// `Time` resource instead.
+///
+/// Methods that are not about a particular domain act on `DomainId::GAME`.
+pub struct AdvancedTime {
+    clock: Box<dyn Clock>,
+    /// `None` until the first update, which only records the time.
+    last_timestamp: Option<f64>,
+    resynced: bool,
+    domains: Vec<(DomainId, TimeDomain)>,
+    unscaled_delta_ms: f64,
+    unscaled_elapsed_ms: f64,
+    smoothed_delta_ms: f64,
+    /// Weight of the newest frame in `smoothed_delta_ms`, 0 to 1.
+    smoothing: f64,
+}
+
+impl AdvancedTime {
+    /// Uses the platform clock: `performance.now()` in the browser, `Instant`
+    /// natively.
+    pub fn new(sub_ticks_per_tick: u32, ms_per_sub_tick: u32) -> Self {
+        Self::with_clock(clock::platform_clock(), sub_ticks_per_tick, ms_per_sub_tick)
+    }
+
+    /// Reads time from `clock`, e.g. a `MockClock` to step time by hand.
+    pub fn with_clock(clock: Box<dyn Clock>, sub_ticks_per_tick: u32, ms_per_sub_tick: u32) -> Self {
+        let radix = TimeRadix::new(sub_ticks_per_tick, ms_per_sub_tick);
+        Self {
+            clock,
+            last_timestamp: None,
+            resynced: false,
+            domains: [DomainId::GAME, DomainId::UI, DomainId::REAL]
+                .into_iter()
+                .map(|id| (id, TimeDomain::new(radix)))
+                .collect(),
+            unscaled_delta_ms: 0.0,
+            unscaled_elapsed_ms: 0.0,
+            smoothed_delta_ms: 0.0,
+            smoothing: 0.1,
+        } 
+    }
+
+    /// Reads the clock and advances every domain that is not paused.
+    pub fn update(&mut self) {
+        let now_ms = self.clock.now_ms();
+        self.update_at(now_ms);
+    }
+
+    /// Like `update`, but with a reading taken by the caller, such as the
+    /// timestamp `requestAnimationFrame` passes its callback. It must be on the
+    /// same timeline as the clock.
+    pub fn update_at(&mut self, current_timestamp: f64) {
+        let Some(last_timestamp) = self.last_timestamp.replace(current_timestamp) else {
+            self.resynced = true;
+            return;
+        };
+        self.resynced = false;
+
+        let unscaled_ms = (current_timestamp - last_timestamp).max(0.0);
+        self.unscaled_delta_ms = unscaled_ms;
+        self.unscaled_elapsed_ms += unscaled_ms;
+        self.smoothed_delta_ms = if self.smoothed_delta_ms == 0.0 {
+            unscaled_ms
+        } else {
+            self.smoothed_delta_ms + (unscaled_ms - self.smoothed_delta_ms) * self.smoothing
+        };
+
+        for (_, domain) in &mut self.domains {
+            domain.advance(unscaled_ms);
+        }
+    }
+
+    /// Forgets the previous reading, so the next update starts a new baseline
+    /// instead of advancing by the whole gap. Used when the frame loop resumes
+    /// after a pause.
+    pub fn resync(&mut self) {
+        self.last_timestamp = None;
+        self.unscaled_delta_ms = 0.0;
+        for (_, domain) in &mut self.domains {
+            domain.clear_delta();
+        }
+    }
+
+    /// Whether the latest update only set the baseline, as the first one and
+    /// the one after `resync` do.
+    pub fn resynced(&self) -> bool {
+        self.resynced
+    }
+
+    /// The clock reading taken by the latest `update`. Recordings store it so a
+    /// replay can feed the exact same readings back through a `MockClock`.
+    pub fn last_reading(&self) -> Option<f64> {
+        self.last_timestamp
+    }
+
+    /// Adds a domain that starts at zero and runs at scale 1. Returns the
+    /// existing one if the name is taken.
+    pub fn add_domain(&mut self, id: DomainId) -> &mut TimeDomain {
+        let index = match self.domains.iter().position(|(existing, _)| *existing == id) {
+            Some(index) => index,
+            None => {
+                let radix = self.game().radix();
+                self.domains.push((id, TimeDomain::new(radix)));
+                self.domains.len() - 1
+            }
+        };
+        &mut self.domains[index].1
+    }
+
+    pub fn domain(&self, id: DomainId) -> Option<&TimeDomain> {
+        self.domains.iter().find(|(existing, _)| *existing == id).map(|(_, domain)| domain)
+    }
+
+    pub fn domain_mut(&mut self, id: DomainId) -> Option<&mut TimeDomain> {
+        self.domains.iter_mut().find(|(existing, _)| *existing == id).map(|(_, domain)| domain)
+    }
+
+    pub fn game(&self) -> &TimeDomain {
+        &self.domains[0].1
+    }
+
+    pub fn game_mut(&mut self) -> &mut TimeDomain {
+        &mut self.domains[0].1
+    }
+
+    /// Readings of every domain, for the `Time` resource.
+    pub fn snapshot(&self) -> Time {
+        Time {
+            domains: self.domains.iter().map(|(id, domain)| (*id, domain.snapshot())).collect(),
+            unscaled_delta: self.unscaled_delta(),
+            smoothed_delta: self.smoothed_delta(),
+        }
+    }
+
+    /// See `TimeDomain::schedule_event`; runs on game time.
+    pub fn schedule_event<F>(&mut self, delay: MixedRadixTime, payload: F) -> TimerId
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.game_mut().schedule_event(delay, payload)
+    }
+
+    pub fn schedule_repeating<F>(&mut self, interval: MixedRadixTime, payload: F) -> TimerId
+    where
+        F: FnMut(&mut World) + 'static,
+    {
+        self.game_mut().schedule_repeating(interval, payload)
+    }
+
+    pub fn schedule_send<E: Event>(&mut self, delay: MixedRadixTime, event: E) -> TimerId {
+        self.game_mut().schedule_send(delay, event)
+    }
+
+    pub fn schedule_send_repeating<E: Event + Clone>(&mut self, interval: MixedRadixTime, event: E) -> TimerId {
+        self.game_mut().schedule_send_repeating(interval, event)
+    }
+
+    /// Cancels a timer of any domain; timer ids are unique across domains.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        self.domains.iter_mut().any(|(_, domain)| domain.cancel(id))
+    }
+
+    pub fn pause_timer(&mut self, id: TimerId) -> bool {
+        self.domains.iter_mut().any(|(_, domain)| domain.pause_timer(id))
+    }
+
+    pub fn resume_timer(&mut self, id: TimerId) -> bool {
+        self.domains.iter_mut().any(|(_, domain)| domain.resume_timer(id))
+    }
+
+    pub fn is_scheduled(&self, id: TimerId) -> bool {
+        self.domains.iter().any(|(_, domain)| domain.is_scheduled(id))
+    }
+
+    pub fn is_timer_paused(&self, id: TimerId) -> bool {
+        self.domains.iter().any(|(_, domain)| domain.is_timer_paused(id))
+    }
+
+    /// Runs the payloads of every timer that came due in the last `update`,
+    /// domain by domain. The time resource is not borrowed while a payload
+    /// runs, so payloads may schedule or cancel timers themselves.
+    pub fn run_due_timers(world: &mut World) {
+        let due: Vec<(usize, TimerId)> = match world.resources.get_non_send_mut::<AdvancedTime>() {
+            Some(mut time) => time
+                .domains
+                .iter_mut()
+                .enumerate()
+                .flat_map(|(index, (_, domain))| std::mem::take(&mut domain.due_timers).into_iter().map(move |id| (index, id)))
+                .collect(),
+            None => return,
+        };
+        for (index, id) in due {
+            let payload = world.resources.get_non_send_mut::<AdvancedTime>().and_then(|mut time| {
+                time.domains[index].1.timers.get_mut(&id).and_then(|timer| timer.payload.take())
+            });
+            let Some(mut payload) = payload else { continue };
+            payload(world);
+
+            if let Some(mut time) = world.resources.get_non_send_mut::<AdvancedTime>() {
+                let timers = &mut time.domains[index].1.timers;
+                let finished = match timers.get_mut(&id) {
+                    Some(timer) if timer.interval.is_some() => {
+                        timer.payload = Some(payload);
+                        false
+                    }
+                    Some(_) => true,
+                    None => false,
+                };
+                if finished {
+                    timers.remove(&id);
+                }
+            }
+        }
+    }
+
+    /// See `TimeDomain::get_delta_time`; game time.
+    pub fn get_delta_time(&self) -> u32 {
+        self.game().get_delta_time()
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.game().elapsed_ms()
+    }
+
+    pub fn now_time(&self) -> MixedRadixTime {
+        self.game().now_time()
+    }
+
+    pub fn radix(&self) -> TimeRadix {
+        self.game().radix()
+    }
+
+    pub fn delta(&self) -> Duration {
+        self.game().delta()
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.game().delta_seconds()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.game().elapsed()
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.game().elapsed_seconds()
+    }
+
+    /// Wall-clock time since the previous update, regardless of any domain's
+    /// scale or pause state.
+    pub fn unscaled_delta(&self) -> Duration {
+        Duration::from_secs_f64(self.unscaled_delta_ms / 1000.0)
+    }
+
+    pub fn unscaled_delta_seconds(&self) -> f32 {
+        (self.unscaled_delta_ms / 1000.0) as f32
+    }
+
+    /// Wall-clock time since the first update.
+    pub fn unscaled_elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.unscaled_elapsed_ms / 1000.0)
+    }
+
+    /// Exponential moving average of the unscaled frame time, for frame-rate
+    /// displays and profiling.
+    pub fn smoothed_delta(&self) -> Duration {
+        Duration::from_secs_f64(self.smoothed_delta_ms / 1000.0)
+    }
+
+    /// How much the newest frame counts in `smoothed_delta`, clamped to 0..1.
+    /// Higher reacts faster; the default is 0.1.
+    pub fn set_smoothing(&mut self, smoothing: f64) {
+        self.smoothing = smoothing.clamp(0.0, 1.0);
+    }
+
+    /// Length of one tick in milliseconds.
+    pub fn tick_ms(&self) -> u32 {
+        self.game().tick_ms()
+    }
+
+    /// Whole game-time ticks since the last call; feeds `FixedTime`.
+    pub fn take_ticks(&mut self) -> u64 {
+        self.game_mut().take_ticks()
+    }
+
+    /// Scales game time only; use `domain_mut` for the others.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.game_mut().set_time_scale(scale);
+    }
+
+    /// Pauses game time only, so UI and real time keep running.
+    pub fn pause(&mut self) {
+        self.game_mut().pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.game_mut().resume();
+    }
+
+    pub fn get_interpolation_factor(&self) -> f32 {
+        self.game().get_interpolation_factor()
+    }
+}
+
+/// One domain's readings for the current frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeSnapshot {
+    pub delta: Duration,
+    pub elapsed: Duration,
+    pub time_scale: f32,
+    pub paused: bool,
+    pub frame_count: u64,
+}
+
+impl TimeSnapshot {
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn elapsed_seconds(&self) -> f64 {
+        self.elapsed.as_secs_f64()
+    }
+}
+
+/// Copy of every domain's readings, refreshed once per frame from
+/// `AdvancedTime`. Unlike `AdvancedTime` it is an ordinary resource, so any
+/// system, parallel ones included, can read the domain it cares about.
+#[derive(Clone, Debug, Default)]
+pub struct Time {
+    domains: HashMap<DomainId, TimeSnapshot>,
+    unscaled_delta: Duration,
+    smoothed_delta: Duration,
+}
+
+impl Time {
+    /// Zeroed readings for an unknown domain.
+    pub fn domain(&self, id: DomainId) -> TimeSnapshot {
+        self.domains.get(&id).copied().unwrap_or_default()
+    }
+
+    pub fn game(&self) -> TimeSnapshot {
+        self.domain(DomainId::GAME)
+    }
+
+    pub fn ui(&self) -> TimeSnapshot {
+        self.domain(DomainId::UI)
+    }
+
+    pub fn real(&self) -> TimeSnapshot {
+        self.domain(DomainId::REAL)
+    }
+
+    pub fn unscaled_delta(&self) -> Duration {
+        self.unscaled_delta
+    }
+
+    pub fn smoothed_delta(&self) -> Duration {
+        self.smoothed_delta
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use crate::ecs_core::resource::NonSendMut;
+    use crate::engine_core::clock::MockClock;
+
+    fn radix() -> impl Strategy<Value = TimeRadix> {
+        (1..100u32, 1..100u32).prop_map(|(sub_ticks_per_tick, ms_per_sub_tick)| TimeRadix::new(sub_ticks_per_tick, ms_per_sub_tick))
+    }
+
+    fn time() -> impl Strategy<Value = MixedRadixTime> {
+        (0..1_000u64, 0..1_000_000u64, radix())
+            .prop_map(|(frames, ms, radix)| MixedRadixTime { frame_count: frames, ..MixedRadixTime::from_ms(ms, radix) })
+    }
+
+    fn is_normalized(time: &MixedRadixTime) -> bool {
+        time.sub_ticks < time.radix.sub_ticks_per_tick && time.milliseconds < time.radix.ms_per_sub_tick
+    }
+
+    proptest! {
+        #[test]
+        fn from_parts_carries_without_losing_time(
+            ticks in 0..1_000u64,
+            sub_ticks in 0..10_000u32,
+            milliseconds in 0..10_000u32,
+            radix in radix(),
+        ) {
+            let time = MixedRadixTime::from_parts(0, ticks, sub_ticks, milliseconds, radix);
+            prop_assert!(is_normalized(&time));
+            let expected = ticks * radix.ms_per_tick()
+                + sub_ticks as u64 * radix.ms_per_sub_tick() as u64
+                + milliseconds as u64;
+            prop_assert_eq!(time.as_ms(), expected);
+        }
+
+        #[test]
+        fn advance_stays_normalized(mut time in time(), deltas in prop::collection::vec(0..5_000u32, 0..20)) {
+            let (start_ms, start_frames) = (time.as_ms(), time.frame_count());
+            for &delta in &deltas {
+                time.advance(delta);
+                prop_assert!(is_normalized(&time));
+            }
+            prop_assert_eq!(time.as_ms(), start_ms + deltas.iter().map(|&delta| delta as u64).sum::<u64>());
+            prop_assert_eq!(time.frame_count(), start_frames + deltas.len() as u64);
+        }
+
+        #[test]
+        fn radix_changes_keep_the_time(time in time(), other in radix()) {
+            let converted = time.with_radix(other);
+            prop_assert!(is_normalized(&converted));
+            prop_assert_eq!(converted, time);
+        }
+
+        #[test]
+        fn ordering_is_by_time_then_frames(a in time(), b in time()) {
+            prop_assert_eq!(a.cmp(&b), (a.as_ms(), a.frame_count()).cmp(&(b.as_ms(), b.frame_count())));
+            prop_assert_eq!(a.cmp(&b), b.cmp(&a).reverse());
+            prop_assert!(a + b >= a);
+        }
+
+        #[test]
+        fn subtraction_undoes_addition(a in time(), b in time()) {
+            let sum = a + b;
+            prop_assert!(is_normalized(&sum));
+            prop_assert_eq!(sum - b, a);
+            prop_assert_eq!(sum.checked_sub(&b), Some(a));
+        }
+
+        #[test]
+        fn timers_fire_in_deadline_order(
+            delays in prop::collection::vec(0..500u64, 1..30),
+            frames in prop::collection::vec(0..100u32, 1..30),
+        ) {
+            let mut domain = TimeDomain::new(TimeRadix::default());
+            let ids: Vec<(u64, TimerId)> = delays
+                .iter()
+                .map(|&delay| (delay, domain.schedule_event(MixedRadixTime::from_ms(delay, domain.radix()), |_| {})))
+                .collect();
+
+            let mut fired = Vec::new();
+            for &frame in &frames {
+                domain.advance(frame as f64);
+                let now = domain.elapsed_ms();
+                for id in std::mem::take(&mut domain.due_timers) {
+                    let &(delay, _) = ids.iter().find(|(_, timer)| *timer == id).unwrap();
+                    prop_assert!(delay <= now);
+                    fired.push((delay, id));
+                }
+            }
+
+            let mut expected: Vec<(u64, TimerId)> =
+                ids.iter().copied().filter(|&(delay, _)| delay <= domain.elapsed_ms()).collect();
+            expected.sort();
+            prop_assert_eq!(fired, expected);
+        }
+    }
+
+    /// 16 ms ticks on a clock that starts at 1000 ms.
+    fn mock_time() -> (MockClock, AdvancedTime) {
+        let clock = MockClock::new(1000.0);
+        let mut time = AdvancedTime::with_clock(Box::new(clock.clone()), 4, 4);
+        time.update();
+        (clock, time)
+    }
+
+    #[test]
+    fn paused_domain_stands_still_while_others_run() {
+        let (clock, mut time) = mock_time();
+        time.game_mut().pause();
+        clock.advance(100.0);
+        time.update();
+
+        assert_eq!(time.game().delta(), Duration::ZERO);
+        assert_eq!(time.game().elapsed(), Duration::ZERO);
+        assert_eq!(time.domain(DomainId::UI).unwrap().elapsed(), Duration::from_millis(100));
+        assert_eq!(time.domain(DomainId::REAL).unwrap().elapsed(), Duration::from_millis(100));
+        assert_eq!(time.take_ticks(), 0);
+
+        time.game_mut().resume();
+        clock.advance(10.0);
+        time.update();
+        assert_eq!(time.game().elapsed(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn domains_scale_independently() {
+        let (clock, mut time) = mock_time();
+        time.game_mut().set_time_scale(0.5);
+        time.add_domain(DomainId("replay")).set_time_scale(2.0);
+        clock.advance(10.0);
+        time.update();
+
+        assert_eq!(time.game().elapsed(), Duration::from_millis(5));
+        assert_eq!(time.domain(DomainId("replay")).unwrap().elapsed(), Duration::from_millis(20));
+        assert_eq!(time.unscaled_delta(), Duration::from_millis(10));
+        let snapshot = time.snapshot();
+        assert_eq!(snapshot.domain(DomainId::GAME).time_scale, 0.5);
+        assert_eq!(snapshot.domain(DomainId("replay")).elapsed, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn timers_follow_their_own_domain() {
+        let (clock, mut time) = mock_time();
+        let game = time.schedule_event(MixedRadixTime::from_ms(10, time.radix()), |_| {});
+        let radix = time.radix();
+        let ui = time.domain_mut(DomainId::UI).unwrap().schedule_event(MixedRadixTime::from_ms(10, radix), |_| {});
+        time.game_mut().pause();
+        clock.advance(20.0);
+        time.update();
+
+        assert_eq!(time.domain(DomainId::UI).unwrap().due_timers, [ui]);
+        assert!(time.game().due_timers.is_empty());
+        assert!(time.is_scheduled(game));
+    }
+
+    fn ms(ms: u64) -> MixedRadixTime {
+        MixedRadixTime::from_ms(ms, TimeRadix::default())
+    }
+
+    #[test]
+    fn cancelled_timers_never_fire() {
+        let mut domain = TimeDomain::new(TimeRadix::default());
+        let cancelled = domain.schedule_event(ms(10), |_| {});
+        let kept = domain.schedule_event(ms(10), |_| {});
+        assert!(domain.cancel(cancelled));
+        assert!(!domain.cancel(cancelled));
+
+        domain.advance(10.0);
+        assert_eq!(domain.due_timers, [kept]);
+        assert!(!domain.is_scheduled(cancelled));
+    }
+
+    #[test]
+    fn paused_timers_keep_their_remaining_time() {
+        let mut domain = TimeDomain::new(TimeRadix::default());
+        let id = domain.schedule_event(ms(10), |_| {});
+        domain.advance(4.0);
+        assert!(domain.pause_timer(id));
+        assert!(!domain.pause_timer(id));
+        assert!(domain.is_timer_paused(id));
+
+        domain.advance(100.0);
+        assert!(domain.due_timers.is_empty());
+        assert!(domain.resume_timer(id));
+        domain.advance(5.0);
+        assert!(domain.due_timers.is_empty());
+        domain.advance(1.0);
+        assert_eq!(domain.due_timers, [id]);
+    }
+
+    #[test]
+    fn repeating_timers_stay_in_phase() {
+        let mut domain = TimeDomain::new(TimeRadix::default());
+        let id = domain.schedule_repeating(ms(10), |_| {});
+        let mut fired = 0;
+        for _ in 0..7 {
+            domain.advance(5.0);
+            fired += std::mem::take(&mut domain.due_timers).len();
+        }
+        assert_eq!(fired, 3);
+        assert!(domain.is_scheduled(id));
+    }
+
+    #[test]
+    fn repeating_timers_fire_once_after_a_stall() {
+        let mut domain = TimeDomain::new(TimeRadix::default());
+        let id = domain.schedule_repeating(ms(10), |_| {});
+        domain.advance(1000.0);
+        assert_eq!(std::mem::take(&mut domain.due_timers), [id]);
+
+        // Rescheduled from the stall, not from the missed deadlines.
+        domain.advance(9.0);
+        assert!(domain.due_timers.is_empty());
+        domain.advance(1.0);
+        assert_eq!(domain.due_timers, [id]);
+    }
+
+    fn world_time(world: &World) -> NonSendMut<'_, AdvancedTime> {
+        world.resources.get_non_send_mut::<AdvancedTime>().unwrap()
+    }
+
+    #[test]
+    fn payloads_run_with_the_world_and_may_cancel_themselves() {
+        let mut world = World::new();
+        world.insert_time(AdvancedTime::with_clock(Box::new(MockClock::new(0.0)), 10, 10));
+        world.insert_resource(0u32);
+
+        let own_id = Rc::new(Cell::new(None));
+        let id = world_time(&world).schedule_repeating(ms(1), {
+            let own_id = own_id.clone();
+            move |world: &mut World| {
+                let runs = {
+                    let mut runs = world.resource_mut::<u32>().unwrap();
+                    *runs += 1;
+                    *runs
+                };
+                if runs == 2 {
+                    world_time(world).cancel(own_id.get().unwrap());
+                }
+            }
+        });
+        own_id.set(Some(id));
+
+        for _ in 0..4 {
+            world_time(&world).game_mut().advance(1.0);
+            AdvancedTime::run_due_timers(&mut world);
+        }
+        assert_eq!(*world.resource::<u32>().unwrap(), 2);
+        assert!(!world_time(&world).is_scheduled(id));
+    }
+}
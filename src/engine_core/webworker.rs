@@ -1,9 +1,245 @@
+// webworker.rs
+// Pool of Web Workers sharing this module's memory, used by the scheduler to run
+// batches of parallel systems in the browser. Needs the crate built with atomics
+// (see `build-web.sh`) and the page served cross-origin isolated (COOP/COEP) so
+// the memory can be shared. Waiting for a batch parks the thread, which the
+// browser only allows in workers, so the pool is only used by schedules run
+// from one; everywhere else batches run on the calling thread.
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use std::cell::RefCell;
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use std::sync::{Condvar, Mutex};
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use wasm_bindgen::{prelude::*, JsCast};
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use web_sys::{Worker, WorkerOptions, WorkerType};
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+use crate::ecs_core::borrow;
+
 pub struct WebWorker {
+    #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+    workers: Vec<Worker>,
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+thread_local! {
+    static POOL: RefCell<Option<WebWorker>> = RefCell::new(None);
+}
+
+/// Served next to the page; see `worker.js`.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+const WORKER_SCRIPT: &str = "./worker.js";
 
+/// A job handed to a worker by pointer.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+struct Task {
+    job: Box<dyn FnOnce() + Send>,
+}
+
+/// What the submitting thread parks on while workers run a batch's jobs.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+struct Batch {
+    pending: Mutex<Pending>,
+    done: Condvar,
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+struct Pending {
+    remaining: usize,
+    failed: bool,
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+impl Batch {
+    fn finish(&self, failed: bool) {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        pending.failed |= failed;
+        pending.remaining -= 1;
+        if pending.remaining == 0 {
+            self.done.notify_all();
+        }
+    }
+
+    /// Parks until every job has finished; `true` if one of them failed.
+    fn wait(&self) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        while pending.remaining != 0 {
+            pending = self.done.wait(pending).unwrap_or_else(|poisoned| poisoned.into_inner());
+        }
+        pending.failed
+    }
+}
+
+/// Whether this thread may park, i.e. is not the browser's main thread.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+fn can_block() -> bool {
+    web_sys::window().is_none()
 }
 
 impl WebWorker {
+    /// A pool with no workers; batches run on the calling thread.
     pub fn new() -> Self {
-        Self { }    
+        Self {
+            #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+            workers: Vec::new(),
+        }
+    }
+
+    /// Starts `count` module workers from `script_url` (see `worker.js`) and
+    /// waits until each has instantiated the shared module.
+    #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+    pub async fn spawn(count: usize, script_url: &str) -> Result<Self, JsValue> {
+        let options = WorkerOptions::new();
+        options.set_type(WorkerType::Module);
+
+        let mut workers = Vec::with_capacity(count);
+        for _ in 0..count {
+            let worker = Worker::new_with_options(script_url, &options)?;
+            let ready = js_sys::Promise::new(&mut |resolve, _reject| {
+                worker.set_onmessage(Some(resolve.unchecked_ref()));
+            });
+
+            let init = js_sys::Object::new();
+            js_sys::Reflect::set(&init, &"type".into(), &"init".into())?;
+            js_sys::Reflect::set(&init, &"module".into(), &wasm_bindgen::module())?;
+            js_sys::Reflect::set(&init, &"memory".into(), &wasm_bindgen::memory())?;
+            worker.post_message(&init)?;
+
+            wasm_bindgen_futures::JsFuture::from(ready).await?;
+            worker.set_onmessage(None);
+            workers.push(worker);
+        }
+        Ok(Self { workers })
+    }
+
+    /// The pool for the calling thread: a worker per spare hardware thread when
+    /// the build has atomics, the page is cross-origin isolated and the caller
+    /// is itself a worker, otherwise none.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn for_browser() -> Self {
+        #[cfg(target_feature = "atomics")]
+        {
+            let isolated = js_sys::Reflect::get(&js_sys::global(), &"crossOriginIsolated".into())
+                .ok()
+                .and_then(|value| value.as_bool())
+                .unwrap_or(false);
+            if !isolated {
+                tracing::warn!("page is not cross-origin isolated; systems run on the main thread");
+                return Self::new();
+            }
+            if !can_block() {
+                tracing::info!("the main browser thread cannot wait for workers; systems run on it");
+                return Self::new();
+            }
+            let threads = js_sys::Reflect::get(&js_sys::global(), &"navigator".into())
+                .and_then(|navigator| js_sys::Reflect::get(&navigator, &"hardwareConcurrency".into()))
+                .ok()
+                .and_then(|threads| threads.as_f64())
+                .unwrap_or(1.0);
+            let count = (threads as usize).saturating_sub(1).max(1);
+            match Self::spawn(count, WORKER_SCRIPT).await {
+                Ok(pool) => pool,
+                Err(e) => {
+                    tracing::error!("Failed to start web workers: {:?}", e);
+                    Self::new()
+                }
+            }
+        }
+
+        #[cfg(not(target_feature = "atomics"))]
+        {
+            tracing::info!("built without atomics; systems run on the main thread");
+            Self::new()
+        }
+    }
+
+    /// Makes this the pool the scheduler uses on the current thread.
+    pub fn install(self) {
+        #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+        POOL.with(|pool| *pool.borrow_mut() = Some(self));
     }
-}
\ No newline at end of file
+
+    /// Runs every job to completion before returning. The first job runs on the
+    /// calling thread; the rest go round-robin to the installed pool, if there
+    /// is one and the calling thread may park while they run. Panics once every
+    /// job has finished if one of them panicked on a worker.
+    pub fn run_scoped<'a>(jobs: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+        #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+        {
+            let jobs = POOL.with(|pool| match pool.borrow().as_ref() {
+                Some(pool) if !pool.workers.is_empty() && can_block() => {
+                    pool.dispatch(jobs);
+                    None
+                }
+                _ => Some(jobs),
+            });
+            if let Some(jobs) = jobs {
+                for job in jobs {
+                    job();
+                }
+            }
+        }
+
+        #[cfg(not(all(target_arch = "wasm32", target_feature = "atomics")))]
+        for job in jobs {
+            job();
+        }
+    }
+
+    #[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+    fn dispatch<'a>(&self, jobs: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+        let mut jobs = jobs.into_iter();
+        let Some(first) = jobs.next() else { return };
+        let batch = Batch { pending: Mutex::new(Pending { remaining: jobs.len(), failed: false }), done: Condvar::new() };
+        let batch_ptr = &batch as *const Batch as u32;
+
+        for (i, job) in jobs.enumerate() {
+            // SAFETY: the job may borrow from the caller for `'a`, which ends
+            // when `run_scoped` returns. We do not return before `batch.wait`
+            // sees every job finished or reported failed, and a worker touches
+            // neither the job nor `batch` after reporting, so no borrow is used
+            // past `'a`. A job that trapped released its borrows first.
+            let job: Box<dyn FnOnce() + Send> = unsafe { std::mem::transmute(job) };
+            let task = Box::into_raw(Box::new(Task { job })) as u32;
+            let worker = &self.workers[i % self.workers.len()];
+            let message = js_sys::Array::of2(&JsValue::from(task), &JsValue::from(batch_ptr));
+            if worker.post_message(&message).is_err() {
+                // Could not hand it off; run it here instead.
+                worker_entry_point(task, batch_ptr);
+            }
+        }
+
+        first();
+        if batch.wait() {
+            panic!("a system running on a web worker panicked");
+        }
+    }
+}
+
+impl Default for WebWorker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Called from `worker.js` with the pointers posted by `WebWorker::dispatch`.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+#[wasm_bindgen]
+pub fn worker_entry_point(task: u32, batch: u32) {
+    let task = unsafe { Box::from_raw(task as *mut Task) };
+    (task.job)();
+    unsafe { (*(batch as *const Batch)).finish(false) };
+}
+
+/// Called from `worker.js` when `worker_entry_point` threw. Panics abort on
+/// wasm, so no guard inside the job ran: the storage and resource borrows it
+/// held are released here, or the next frame would find them still taken.
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+#[wasm_bindgen]
+pub fn worker_task_failed(batch: u32) {
+    // SAFETY: the world the job borrowed from outlives the batch, which waits
+    // for this call, and the trapped job's guards are gone with its stack.
+    unsafe { borrow::release_thread_borrows() };
+    unsafe { (*(batch as *const Batch)).finish(true) };
+}
@@ -1,35 +1,59 @@
 // resources.rs
 // non ECS based resources needed for the core of the engine... eg webGPUResources struct.
 
+use std::fmt;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::JsValue;
+#[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
-use wgpu::{self, Backends, Surface};
-use tracing::{error};
+use wgpu::{self, Surface, SurfaceError};
+use tracing::{error, info};
+use crate::engine_core::renderer_config::{CapabilityReport, RendererConfig};
+#[cfg(any(target_arch = "wasm32", test))]
+use tracing::warn;
 
 pub trait EngineResources {
     fn get_instance(&self) -> &wgpu::Instance;
     fn get_device(&self) -> &wgpu::Device;
     fn get_queue(&self) -> &wgpu::Queue;
     fn get_adapter(&self) -> &wgpu::Adapter;
-    fn get_surface(&self) -> &wgpu::Surface;
+    /// `None` when rendering offscreen.
+    fn get_surface(&self) -> Option<&wgpu::Surface<'_>>;
     fn get_config(&self) -> &wgpu::SurfaceConfiguration;
     fn resize(&mut self, width: u32, height: u32);
 }
 
+/// Where frames end up.
+enum RenderTarget {
+    #[cfg(target_arch = "wasm32")]
+    Canvas { canvas: HtmlCanvasElement, surface: Surface<'static> },
+    /// A texture nothing presents; read it back with `read_frame`.
+    Offscreen { texture: wgpu::Texture },
+}
+
 pub struct WebGPUResources {
-    canvas: HtmlCanvasElement,
+    target: RenderTarget,
     instance: wgpu::Instance,
     adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
+    /// Size and format of the target. Only handed to a surface when there is
+    /// one.
     config: wgpu::SurfaceConfiguration,
 }
 
 impl WebGPUResources {
-    pub async fn new(canvas: HtmlCanvasElement) -> Result<Self, JsValue> {
+    /// Renders to `canvas` with the default `RendererConfig`.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn new(canvas: HtmlCanvasElement) -> Result<Self, RendererError> {
+        Self::with_config(canvas, &RendererConfig::default()).await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub async fn with_config(canvas: HtmlCanvasElement, renderer_config: &RendererConfig) -> Result<Self, RendererError> {
 
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-            backends: Backends::PRIMARY,
+            backends: renderer_config.wgpu_backends(),
             dx12_shader_compiler: Default::default(),
             flags: Default::default(),
             gles_minor_version: Default::default()
@@ -39,33 +63,10 @@ impl WebGPUResources {
         let surface = instance.create_surface(surface_target)
         .map_err(|e| {
             error!("Failed to create surface: {}", e);
-            JsValue::from_str(&format!("Could not create surface: {}", e)) 
+            RendererError::Surface(e)
         })?;
 
-        let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            force_fallback_adapter: false,    
-            compatible_surface: Some(&surface),
-        })
-        .await
-        .ok_or_else(|| { 
-            let err_msg = "No Suitable GPU adapter found";
-            error!("{}", err_msg);
-            JsValue::from_str(err_msg)
-        })?;
-
-        let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
-            label: None,
-            required_features: wgpu::Features::empty(),
-            required_limits: wgpu::Limits::default(),
-            memory_hints: wgpu::MemoryHints::default(),
-            },
-        None,
-        ).await
-        .map_err(|e| { 
-            error!("Failed to create device: {}", e);
-            JsValue::from_str(&format!("Failed to create device: {}", e))
-        })?;
+        let (adapter, device, queue) = request_device(&instance, Some(&surface), false, renderer_config).await?;
 
         let surface_caps = surface.get_capabilities(&adapter);
         let surface_format = surface_caps
@@ -74,28 +75,325 @@ impl WebGPUResources {
             .copied()
             .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
+        let present_mode = renderer_config.present_mode.choose(&surface_caps.present_modes);
+        let preferred_alpha: &[wgpu::CompositeAlphaMode] = if renderer_config.transparent {
+            &[wgpu::CompositeAlphaMode::PreMultiplied, wgpu::CompositeAlphaMode::PostMultiplied, wgpu::CompositeAlphaMode::Inherit]
+        } else {
+            &[wgpu::CompositeAlphaMode::Opaque]
+        };
+        let alpha_mode = preferred_alpha
+            .iter()
+            .copied()
+            .find(|mode| surface_caps.alpha_modes.contains(mode))
+            .unwrap_or(surface_caps.alpha_modes[0]);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: canvas.width(),
-            height: canvas.height(),
-            present_mode: surface_caps.present_modes[0],
+            width: canvas.width().max(1),
+            height: canvas.height().max(1),
+            present_mode,
             desired_maximum_frame_latency: 2,
-            alpha_mode: surface_caps.alpha_modes[0],
+            alpha_mode,
             view_formats: vec![],
         };
         surface.configure(&device, &config);
 
+        Ok(Self {
+            target: RenderTarget::Canvas { canvas, surface },
+            instance,
+            adapter,
+            device,
+            queue,
+            config })
+    }
+
+    /// Renders into a `width` x `height` RGBA texture instead of a window, for
+    /// tests and tools. Takes any adapter on any backend, and falls back to a
+    /// software one (e.g. lavapipe or llvmpipe on Linux) when there is no GPU.
+    pub async fn new_offscreen(width: u32, height: u32) -> Result<Self, RendererError> {
+        Self::offscreen_with_config(width, height, &RendererConfig::default()).await
+    }
+
+    /// `new_offscreen` with the adapter and device picked by `renderer_config`.
+    /// Its presentation settings do not apply.
+    pub async fn offscreen_with_config(width: u32, height: u32, renderer_config: &RendererConfig) -> Result<Self, RendererError> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: renderer_config.wgpu_backends(),
+            dx12_shader_compiler: Default::default(),
+            flags: Default::default(),
+            gles_minor_version: Default::default()
+        });
+
+        let (adapter, device, queue) = match request_device(&instance, None, false, renderer_config).await {
+            Err(RendererError::NoAdapter) => request_device(&instance, None, true, renderer_config).await?,
+            result => result?,
+        };
 
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            desired_maximum_frame_latency: 2,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+        };
+        let texture = create_offscreen_texture(&device, &config);
 
         Ok(Self {
-            canvas: canvas.clone(), 
-            instance, 
-            // surface, 
-            adapter, 
-            device, 
-            queue, 
-            config })
+            target: RenderTarget::Offscreen { texture },
+            instance,
+            adapter,
+            device,
+            queue,
+            config,
+        })
+    }
+
+    pub fn is_offscreen(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen { .. })
+    }
+
+    /// The next texture to draw into. Presented when the returned frame is
+    /// dropped. A lost or outdated surface is reconfigured (picking up the
+    /// canvas's current size) and acquired once more; `Timeout` means skip
+    /// this frame, `OutOfMemory` is fatal. Offscreen, this is always the same
+    /// texture.
+    pub fn acquire_frame(&mut self) -> Result<Frame, SurfaceError> {
+        match &self.target {
+            #[cfg(target_arch = "wasm32")]
+            RenderTarget::Canvas { .. } => self.acquire_surface_frame(),
+            RenderTarget::Offscreen { texture } => {
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                Ok(Frame { surface_texture: None, view })
+            }
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn acquire_surface_frame(&mut self) -> Result<Frame, SurfaceError> {
+        acquire_with_retry(
+            self,
+            |gpu| gpu.get_surface().expect("only called for canvas targets").get_current_texture(),
+            |texture| texture.suboptimal,
+            Self::reconfigure,
+        )
+        .map(Frame::new)
+    }
+
+    /// Configures the surface again at the canvas's current size.
+    #[cfg(target_arch = "wasm32")]
+    fn reconfigure(&mut self) {
+        if let RenderTarget::Canvas { canvas, .. } = &self.target {
+            let (width, height) = (canvas.width(), canvas.height());
+            self.resize(width, height);
+        }
+    }
+
+    /// Copies the offscreen texture into an image, waiting for the GPU to
+    /// finish everything submitted so far. Native only, where every target is
+    /// offscreen; the browser's main thread cannot block on the GPU.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_frame(&self) -> Result<image::RgbaImage, RendererError> {
+        let RenderTarget::Offscreen { texture } = &self.target;
+        let (width, height) = (self.config.width, self.config.height);
+        let unpadded_bytes_per_row = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(align) * align;
+
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame readback"),
+            size: padded_bytes_per_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("frame readback"),
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|_| RendererError::Readback(None))?
+            .map_err(|e| RendererError::Readback(Some(e)))?;
+
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+        for row in slice.get_mapped_range().chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        buffer.unmap();
+        Ok(image::RgbaImage::from_raw(width, height, pixels).expect("buffer holds exactly one frame"))
+    }
+}
+
+/// Asks for an adapter that can drive `compatible_surface` (if any), then for
+/// a device with the features and limits `config` asks for.
+async fn request_device(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&Surface<'_>>,
+    force_fallback_adapter: bool,
+    config: &RendererConfig,
+) -> Result<(wgpu::Adapter, wgpu::Device, wgpu::Queue), RendererError> {
+    let adapter = instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: config.power_preference.to_wgpu(),
+        force_fallback_adapter,
+        compatible_surface,
+    })
+    .await
+    .ok_or_else(|| {
+        error!("No Suitable GPU adapter found");
+        RendererError::NoAdapter
+    })?;
+
+    let (report, required_limits) = CapabilityReport::check(config, &adapter);
+    if !report.is_supported() {
+        error!("{}", report);
+        return Err(RendererError::Unsupported(report));
+    }
+    let optional_features = config.optional_wgpu_features();
+    let available_optional = optional_features & adapter.features();
+    if available_optional != optional_features {
+        info!("optional GPU features not available: {:?}", optional_features - available_optional);
+    }
+
+    let (device, queue) = adapter.request_device(&wgpu::DeviceDescriptor {
+        label: None,
+        required_features: config.required_wgpu_features() | available_optional,
+        required_limits,
+        memory_hints: wgpu::MemoryHints::default(),
+        },
+    None,
+    ).await
+    .map_err(|e| { 
+        error!("Failed to create device: {}", e);
+        RendererError::Device(e)
+    })?;
+
+    Ok((adapter, device, queue))
+}
+
+/// Acquires a surface texture, reconfiguring and trying once more if the first
+/// was suboptimal, lost or outdated. Takes the surface's owner as `target` so
+/// the policy can be tested without one.
+#[cfg(any(target_arch = "wasm32", test))]
+fn acquire_with_retry<S, T>(
+    target: &mut S,
+    acquire: impl Fn(&S) -> Result<T, SurfaceError>,
+    suboptimal: impl Fn(&T) -> bool,
+    reconfigure: impl Fn(&mut S),
+) -> Result<T, SurfaceError> {
+    match acquire(target) {
+        Ok(texture) if !suboptimal(&texture) => return Ok(texture),
+        // Still usable, but reconfiguring gets a better match next time.
+        Ok(texture) => {
+            drop(texture);
+            reconfigure(target);
+        }
+        Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+            warn!("surface lost or outdated; reconfiguring");
+            reconfigure(target);
+        }
+        Err(e) => return Err(e),
+    }
+    acquire(target)
+}
+
+fn create_offscreen_texture(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("offscreen frame"),
+        size: wgpu::Extent3d { width: config.width, height: config.height, depth_or_array_layers: 1 },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+/// The texture being drawn for the current frame. Dropping it presents a
+/// surface texture; `discard` drops it without presenting.
+pub struct Frame {
+    /// `None` offscreen.
+    surface_texture: Option<wgpu::SurfaceTexture>,
+    view: wgpu::TextureView,
+}
+
+impl Frame {
+    #[cfg(target_arch = "wasm32")]
+    fn new(texture: wgpu::SurfaceTexture) -> Self {
+        let view = texture.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Self { surface_texture: Some(texture), view }
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Gives the texture back without showing it, e.g. when recording failed.
+    pub fn discard(mut self) {
+        self.surface_texture = None;
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        if let Some(texture) = self.surface_texture.take() {
+            texture.present();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RendererError {
+    Surface(wgpu::CreateSurfaceError),
+    /// No adapter on any enabled backend, not even a software one.
+    NoAdapter,
+    /// The adapter lacks required features or limits.
+    Unsupported(CapabilityReport),
+    Device(wgpu::RequestDeviceError),
+    /// Mapping the readback buffer failed; `None` if the device was lost first.
+    Readback(Option<wgpu::BufferAsyncError>),
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Surface(e) => write!(f, "could not create surface: {}", e),
+            RendererError::NoAdapter => write!(f, "no suitable GPU adapter found"),
+            RendererError::Unsupported(report) => write!(f, "{}", report),
+            RendererError::Device(e) => write!(f, "failed to create device: {}", e),
+            RendererError::Readback(Some(e)) => write!(f, "could not read back frame: {}", e),
+            RendererError::Readback(None) => write!(f, "could not read back frame: device lost"),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {}
+
+#[cfg(target_arch = "wasm32")]
+impl From<RendererError> for JsValue {
+    fn from(error: RendererError) -> Self {
+        JsValue::from_str(&error.to_string())
     }
 }
 
@@ -108,14 +406,14 @@ impl EngineResources for WebGPUResources {
         &self.device
     }
 
-    fn get_surface(&self) -> &wgpu::Surface {
-        let surface_target = wgpu::SurfaceTarget::Canvas(self.canvas.clone());
-        let surface = self.instance.create_surface(surface_target)
-            .expect("Failed to create surface");
-        let surface_box = Box::new(surface);
-        Box::leak(surface_box)
+    fn get_surface(&self) -> Option<&wgpu::Surface<'_>> {
+        match &self.target {
+            #[cfg(target_arch = "wasm32")]
+            RenderTarget::Canvas { surface, .. } => Some(surface),
+            RenderTarget::Offscreen { .. } => None,
+        }
     }
-    
+
     fn get_queue(&self) -> &wgpu::Queue {
         &self.queue
     }
@@ -128,9 +426,100 @@ impl EngineResources for WebGPUResources {
         &self.config
     }
 
+    /// Zero sizes (a hidden or collapsed canvas) are ignored; a surface cannot
+    /// be configured that small. Offscreen, the texture is replaced.
     fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
         self.config.width = width;
         self.config.height = height;
-        self.get_surface().configure(&self.device, &self. config);
+        match &mut self.target {
+            #[cfg(target_arch = "wasm32")]
+            RenderTarget::Canvas { surface, .. } => surface.configure(&self.device, &self.config),
+            RenderTarget::Offscreen { texture } => *texture = create_offscreen_texture(&self.device, &self.config),
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use super::*;
+
+    /// Hands out scripted `(id, suboptimal)` textures and counts reconfigures.
+    struct ScriptedSurface {
+        results: RefCell<VecDeque<Result<(u32, bool), SurfaceError>>>,
+        reconfigured: usize,
+    }
+
+    fn acquire(results: impl IntoIterator<Item = Result<(u32, bool), SurfaceError>>) -> (Result<u32, SurfaceError>, usize) {
+        let mut surface = ScriptedSurface { results: RefCell::new(results.into_iter().collect()), reconfigured: 0 };
+        let result = acquire_with_retry(
+            &mut surface,
+            |surface| surface.results.borrow_mut().pop_front().expect("acquired more often than scripted"),
+            |&(_, suboptimal)| suboptimal,
+            |surface| surface.reconfigured += 1,
+        );
+        (result.map(|(id, _)| id), surface.reconfigured)
+    }
+
+    #[test]
+    fn good_textures_are_used_at_once() {
+        assert_eq!(acquire([Ok((1, false))]), (Ok(1), 0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stale_surfaces_are_reconfigured_once() {
+        assert_eq!(acquire([Ok((1, true)), Ok((2, true))]), (Ok(2), 1));
+        assert_eq!(acquire([Err(SurfaceError::Lost), Ok((2, false))]), (Ok(2), 1));
+        assert_eq!(acquire([Err(SurfaceError::Outdated), Err(SurfaceError::Outdated)]), (Err(SurfaceError::Outdated), 1));
+    }
+
+    #[test]
+    fn other_errors_are_returned_without_retrying() {
+        assert_eq!(acquire([Err(SurfaceError::Timeout)]), (Err(SurfaceError::Timeout), 0));
+        assert_eq!(acquire([Err(SurfaceError::OutOfMemory)]), (Err(SurfaceError::OutOfMemory), 0));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter; run with --ignored"]
+    fn zero_sizes_are_ignored() {
+        let mut gpu = pollster::block_on(WebGPUResources::new_offscreen(4, 2)).unwrap();
+        gpu.resize(0, 8);
+        gpu.resize(8, 0);
+        assert_eq!((gpu.get_config().width, gpu.get_config().height), (4, 2));
+        gpu.resize(6, 3);
+        assert_eq!(gpu.read_frame().unwrap().dimensions(), (6, 3));
+    }
+
+    #[test]
+    #[ignore = "needs a GPU adapter; run with --ignored"]
+    fn offscreen_clear_reads_back() {
+        // Rows of 5 pixels need padding to the copy alignment.
+        let mut gpu = pollster::block_on(WebGPUResources::new_offscreen(5, 3)).unwrap();
+        let frame = gpu.acquire_frame().unwrap();
+        let mut encoder = gpu.get_device().create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame.view(),
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 1.0, g: 0.0, b: 1.0, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        gpu.get_queue().submit(Some(encoder.finish()));
+        drop(frame);
+
+        let image = gpu.read_frame().unwrap();
+        assert_eq!(image.dimensions(), (5, 3));
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 255, 255]));
+    }
+}
@@ -1,29 +1,323 @@
 // world.rs
-use crate::ecs_core::component::ComponentManager;
-use crate::ecs_core::resource::ResourceManager;
-use crate::ecs_core::entity::EntityManager;
-use crate::ecs_core::system::System;
+use std::any::TypeId;
+use std::sync::Mutex;
+use crate::components::transform_component::Transform;
+use crate::ecs_core::bundle::{self, Bundle};
+use crate::ecs_core::command::{CommandQueue, Commands};
+use crate::ecs_core::component::{Component, ComponentManager, RemovedComponents};
+use crate::ecs_core::entity::{Entity, EntityManager};
+use crate::ecs_core::event::{self, Event, EventWriter, Events};
+use crate::ecs_core::query::{Query, QueryData, QueryFilter};
+use crate::ecs_core::resource::{Res, ResMut, ResourceManager};
+use crate::ecs_core::schedule::{Schedule, ScheduleError, Stage, SystemConfig};
+use crate::engine_core::fixed_time::FixedTime;
+use crate::engine_core::inputhandler::{InputEvent, InputHandler};
+use crate::engine_core::replay::{ChecksumFn, FrameRecord, Recorder};
+use crate::engine_core::scene_graph::{Children, Parent};
+use crate::engine_core::temporal::AdvancedTime;
 use crate::systems::input_system::InputSystem;
-use crate::LuminaEngine;
+use crate::systems::mesh_upload_system::MeshUploadSystem;
+use crate::systems::rendering_system::RenderingSystem;
+use crate::systems::timer_system::TimerSystem;
+use crate::systems::transform_system::{TransformPropagationSystem, TransformSnapshotSystem, TRANSFORM_SNAPSHOT};
 
 pub struct World {
     pub entities: EntityManager,
     pub components: ComponentManager,
-    pub systems: Vec<Box<dyn System>>,
+    pub resources: ResourceManager,
+    pub schedule: Schedule,
+    /// Commands recorded outside of any system.
+    command_queue: Mutex<CommandQueue>,
+    event_updaters: Vec<fn(&ResourceManager)>,
+    /// Component types that make up `state_checksum`, in registration order.
+    pub(crate) checksummed: Vec<(TypeId, ChecksumFn)>,
 }
 
 impl World {
-    pub fn new(engine: &'static LuminaEngine) -> Self {
+    pub fn new() -> Self {
         let mut world = Self {
             entities: EntityManager::new(),
             components: ComponentManager::new(),
-            systems: Vec::new(),
+            resources: ResourceManager::new(),
+            schedule: Schedule::new(),
+            command_queue: Mutex::new(CommandQueue::new()),
+            event_updaters: Vec::new(),
+            checksummed: Vec::new(),
         };
+        world.add_event::<InputEvent>();
+        world.checksum_component::<Transform>();
 
         // System initialization
-        world.systems.push(Box::new(InputSystem::new()));
-        // world.systems.push(Box::new(RenderingSystem::new()));
+        world.schedule.add_system(Stage::PreUpdate, InputSystem::new());
+        world.schedule.add_system(
+            Stage::PreUpdate,
+            SystemConfig::new(TimerSystem).after(std::any::type_name::<InputSystem>()),
+        );
+        world.schedule.add_system(
+            Stage::FixedUpdate,
+            SystemConfig::parallel(TransformSnapshotSystem).label(TRANSFORM_SNAPSHOT),
+        );
+        world.schedule.add_system(Stage::PostUpdate, TransformPropagationSystem::new());
+        world.schedule.add_system(
+            Stage::Render,
+            SystemConfig::new(MeshUploadSystem::new()).before(std::any::type_name::<RenderingSystem>()),
+        );
+        world.schedule.add_system(Stage::Render, RenderingSystem::new());
 
         world
     }
-}
\ No newline at end of file
+
+    /// Creates an entity with every component of `bundle`.
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        bundle::assert_unique::<B>();
+        let entity = self.entities.create_entity();
+        bundle.insert(entity, &mut self.components);
+        entity
+    }
+
+    /// Spawns one entity per bundle, reserving storage up front from the
+    /// iterator's size hint.
+    pub fn spawn_batch<B: Bundle>(&mut self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        bundle::assert_unique::<B>();
+        let bundles = bundles.into_iter();
+        let additional = bundles.size_hint().0;
+        B::reserve(&mut self.components, additional);
+        let mut spawned = Vec::with_capacity(additional);
+        for bundle in bundles {
+            let entity = self.entities.create_entity();
+            bundle.insert(entity, &mut self.components);
+            spawned.push(entity);
+        }
+        spawned
+    }
+
+    /// Adds or replaces a component of a live entity. Returns `false`, leaving
+    /// the world untouched, if the entity has been despawned.
+    pub fn insert<C: Component>(&mut self, entity: Entity, component: C) -> bool {
+        self.entities.is_alive(entity) && self.components.insert(entity, component)
+    }
+
+    /// Destroys the entity and strips all of its components. It is dropped from
+    /// its parent's `Children`; its own children stay alive and become roots.
+    /// Returns `false` if the handle was already stale.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.destroy_entity(entity) {
+            return false;
+        }
+        self.detach(entity);
+        let children = self.components.get_mut::<Children>(&entity).map(std::mem::take);
+        if let Some(children) = children {
+            for child in children.iter() {
+                self.components.remove::<Parent>(&child);
+            }
+        }
+        self.components.remove_all(&entity);
+        true
+    }
+
+    /// Queues the entity for despawn at the end of the stage. Only needs `&self`
+    /// so systems can call it while iterating component storages.
+    pub fn despawn_deferred(&self, entity: Entity) {
+        self.commands().despawn(entity);
+    }
+
+    /// A buffer for spawns, despawns, inserts and removals that are applied at
+    /// the end of the current stage. Only needs `&self`.
+    pub fn commands(&self) -> Commands<'_> {
+        Commands::new(&self.entities, &self.command_queue)
+    }
+
+    /// Applies commands recorded outside of systems, then `queues` in order.
+    /// The schedule calls this at the end of every stage with the queues of the
+    /// systems that ran.
+    pub fn apply_commands(&mut self, queues: impl IntoIterator<Item = CommandQueue>) {
+        let own = std::mem::take(self.command_queue.get_mut().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        own.apply(self);
+        for queue in queues {
+            queue.apply(self);
+        }
+    }
+
+    /// See `ComponentManager::query`.
+    pub fn query<Q: QueryData>(&self) -> Query<'_, Q> {
+        self.components.query::<Q>()
+    }
+
+    /// See `ComponentManager::query_filtered`.
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&self) -> Query<'_, Q, F> {
+        self.components.query_filtered::<Q, F>()
+    }
+
+    /// See `ComponentManager::removed`.
+    pub fn removed<C: Component>(&self) -> RemovedComponents<'_, C> {
+        self.components.removed::<C>()
+    }
+
+    /// See `ResourceManager::insert`.
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources.insert(resource)
+    }
+
+    /// See `ResourceManager::get`.
+    pub fn resource<T: Send + Sync + 'static>(&self) -> Option<Res<'_, T>> {
+        self.resources.get::<T>()
+    }
+
+    /// See `ResourceManager::get_mut`.
+    pub fn resource_mut<T: Send + Sync + 'static>(&self) -> Option<ResMut<'_, T>> {
+        self.resources.get_mut::<T>()
+    }
+
+    /// Inserts an `Events<T>` resource and has the schedule swap its buffers at
+    /// the end of every frame. Adding the same type twice does nothing.
+    pub fn add_event<T: Event>(&mut self) {
+        if !self.resources.contains::<Events<T>>() {
+            self.resources.insert(Events::<T>::new());
+            self.event_updaters.push(event::update_events::<T>);
+        }
+    }
+
+    /// Sends an event. Panics if `T` was never added with `add_event`.
+    pub fn send_event<T: Event>(&self, event: T) {
+        self.event_writer::<T>().send(event);
+    }
+
+    /// Panics if `T` was never added with `add_event`.
+    pub fn event_writer<T: Event>(&self) -> EventWriter<'_, T> {
+        let events = self.resources.get_mut::<Events<T>>().unwrap_or_else(|| {
+            panic!("event type {} was not added to the world", std::any::type_name::<T>())
+        });
+        EventWriter::new(events)
+    }
+
+    /// Panics if `T` was never added with `add_event`.
+    pub fn events<T: Event>(&self) -> Res<'_, Events<T>> {
+        self.resources.get::<Events<T>>().unwrap_or_else(|| {
+            panic!("event type {} was not added to the world", std::any::type_name::<T>())
+        })
+    }
+
+    /// Swaps the buffers of every event type. Called by the schedule once all
+    /// stages of a frame have run.
+    pub fn update_events(&mut self) {
+        for update in &self.event_updaters {
+            update(&self.resources);
+        }
+    }
+
+    /// Makes `temporal` the world's clock, along with the `FixedTime` and `Time`
+    /// resources derived from it.
+    pub fn insert_time(&mut self, temporal: AdvancedTime) {
+        self.insert_resource(FixedTime::new(temporal.tick_ms()));
+        self.insert_resource(temporal.snapshot());
+        // Holds a browser handle, so it stays on the main thread.
+        self.resources.insert_non_send(temporal);
+    }
+
+    /// Runs one frame: advances `AdvancedTime`, banks the elapsed ticks on
+    /// `FixedTime`, refreshes `Time`, sends the input queued on `InputHandler`
+    /// as `InputEvent`s, then runs the schedule. Appends the frame to the
+    /// `Recorder` if one is present.
+    pub fn run_frame(&mut self) -> Result<(), ScheduleError> {
+        self.run_frame_with(None)
+    }
+
+    /// `run_frame` with the time read by the caller; see
+    /// `AdvancedTime::update_at`.
+    pub fn run_frame_at(&mut self, timestamp_ms: f64) -> Result<(), ScheduleError> {
+        self.run_frame_with(Some(timestamp_ms))
+    }
+
+    fn run_frame_with(&mut self, timestamp_ms: Option<f64>) -> Result<(), ScheduleError> {
+        let time = self.resources.get_non_send_mut::<AdvancedTime>().map(|mut temporal| {
+            match timestamp_ms {
+                Some(timestamp_ms) => temporal.update_at(timestamp_ms),
+                None => temporal.update(),
+            }
+            let ticks = temporal.take_ticks();
+            if let Some(mut fixed) = self.resource_mut::<FixedTime>() {
+                fixed.accumulate(ticks, temporal.get_interpolation_factor());
+            }
+            (temporal.snapshot(), temporal.last_reading(), temporal.resynced())
+        });
+        let (mut clock_ms, mut resync) = (None, false);
+        if let Some((time, reading, resynced)) = time {
+            self.insert_resource(time);
+            clock_ms = reading;
+            resync = resynced;
+        }
+
+        let inputs = self.resource_mut::<InputHandler>().map(|mut input| input.drain()).unwrap_or_default();
+        for input in &inputs {
+            self.send_event(input.clone());
+        }
+
+        let result = self.run_schedule();
+        if self.resources.contains::<Recorder>() {
+            let checksum = self.state_checksum();
+            if let Some(mut recorder) = self.resource_mut::<Recorder>() {
+                recorder.push(FrameRecord { clock_ms, resync, inputs, checksum });
+            }
+        }
+        result
+    }
+
+    /// Runs every stage of the schedule once. The schedule is moved out while it
+    /// runs so systems can take `&mut World`.
+    pub fn run_schedule(&mut self) -> Result<(), ScheduleError> {
+        let mut schedule = std::mem::take(&mut self.schedule);
+        let result = schedule.run(self);
+        // Keep anything systems added to `world.schedule` while it was out.
+        let added = std::mem::replace(&mut self.schedule, schedule);
+        self.schedule.merge(added);
+        result
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+
+    #[test]
+    fn despawn_strips_every_component() {
+        let mut world = World::new();
+        let entity = world.spawn((Health(3), Name("crate")));
+        let other = world.spawn((Health(5),));
+
+        assert!(world.despawn(entity));
+        assert!(world.components.get::<Health>(&entity).is_none());
+        assert!(world.components.get::<Name>(&entity).is_none());
+        assert_eq!(world.components.get::<Health>(&other).as_deref(), Some(&Health(5)));
+        assert!(!world.despawn(entity));
+
+        // The slot's next occupant starts out empty.
+        let reused = world.spawn((Name("new"),));
+        assert_eq!(reused.index(), entity.index());
+        assert!(world.components.get::<Health>(&reused).is_none());
+    }
+
+    #[test]
+    fn deferred_despawns_wait_for_the_sync_point() {
+        let mut world = World::new();
+        let entity = world.spawn((Health(1),));
+        world.despawn_deferred(entity);
+        world.despawn_deferred(entity);
+
+        assert!(world.entities.is_alive(entity));
+        assert!(world.components.get::<Health>(&entity).is_some());
+        world.apply_commands([]);
+        assert!(!world.entities.is_alive(entity));
+        assert!(world.components.get::<Health>(&entity).is_none());
+    }
+}
@@ -1,62 +1,154 @@
 // lib.rs
-mod components;
-mod engine_core;
-mod ecs_core;
-mod systems;
+// Lets `#[derive(Bundle)]`, which names `::lumina_engine`, work inside the crate too.
+extern crate self as lumina_engine;
+
+pub mod components;
+pub mod engine_core;
+pub mod ecs_core;
+pub mod systems;
 mod tracing;
-use engine_core::core_loop::EngineLoop;
-use engine_core::wgpures::WebGPUResources;
-use engine_core::temporal::{self, AdvancedTime};
+use engine_core::wgpures::{RendererError, WebGPUResources};
+use engine_core::temporal::AdvancedTime;
 use engine_core::networking::NetworkResources;
 use engine_core::rendering::RenderSystem;
 use engine_core::inputhandler::InputHandler;
+#[cfg(target_arch = "wasm32")]
+use engine_core::inputhandler::BrowserInput;
+use engine_core::mesh::Meshes;
+use engine_core::renderer_config::RendererConfig;
+use engine_core::replay::Replayer;
+#[cfg(target_arch = "wasm32")]
 use engine_core::webworker::WebWorker;
+#[cfg(target_arch = "wasm32")]
+use engine_core::core_loop::{EngineHandle, EngineLoop};
 use engine_core::world::World;
 pub use tracing::init_tracing;
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
-use std::rc::Rc;
-
-pub struct EngineResources {
-    pub webgpu_resource: WebGPUResources,
-    pub temporal: AdvancedTime,
-    pub rendering: RenderSystem,
-    pub networking: NetworkResources,
-    pub inputhandler: InputHandler,
-    pub workers: WebWorker,
-}
 
 pub struct LuminaEngine {
-    resources: EngineResources,
-    coreloop: EngineLoop,
     world: World,
 }
 
 impl LuminaEngine {
+    /// Renders to `canvas` with the default `RendererConfig`. Panics if no
+    /// suitable GPU is found.
+    #[cfg(target_arch = "wasm32")]
     pub async fn new(canvas: HtmlCanvasElement) -> Self {
-        let webgpu_resource = WebGPUResources::new(canvas).await.unwrap();
+        Self::with_config(canvas, &RendererConfig::default()).await.unwrap()
+    }
+
+    /// Renders to `canvas` with the adapter and presentation `renderer_config`
+    /// asks for, e.g. one loaded with `RendererConfig::from_json`.
+    #[cfg(target_arch = "wasm32")]
+    pub async fn with_config(canvas: HtmlCanvasElement, renderer_config: &RendererConfig) -> Result<Self, RendererError> {
+        let inputhandler = InputHandler::new();
+        let browser_input = BrowserInput::attach(&canvas, inputhandler.sender());
+        let webgpu_resource = WebGPUResources::with_config(canvas, renderer_config).await?;
         let temporal = AdvancedTime::new(10, 10);
         let rendering = RenderSystem::new();
         let networking = NetworkResources::new();
-        let inputhandler = InputHandler::new();
-        let workers = WebWorker::new();
+        let workers = WebWorker::for_browser().await;
+
+        let mut world = World::new();
+        world.insert_time(temporal);
+        // Holds browser handles, so it stays on the main thread.
+        world.resources.insert_non_send(webgpu_resource);
+        world.insert_resource(rendering);
+        world.insert_resource(Meshes::new());
+        world.insert_resource(networking);
+        world.insert_resource(inputhandler);
+        world.resources.insert_non_send(browser_input);
+        workers.install();
 
-    
+        Ok(Self { world })
+    }
+
+    /// An engine without a window or GPU, for native tools, servers and tests.
+    pub fn headless() -> Self {
+        let mut world = World::new();
+        world.insert_time(AdvancedTime::new(10, 10));
+        Self::headless_from(world)
+    }
 
-        Self {
+    /// A headless engine whose time and input come from `replayer`. Add the
+    /// systems and entities of the recorded session, then replay with
+    /// `replayer.run(engine.world_mut())`.
+    pub fn replaying(replayer: &Replayer) -> Self {
+        Self::headless_from(replayer.headless_world())
+    }
+
+    fn headless_from(mut world: World) -> Self {
+        world.insert_resource(RenderSystem::new());
+        world.insert_resource(Meshes::new());
+        world.insert_resource(NetworkResources::new());
+        world.insert_resource(InputHandler::new());
+
+        Self { world }
+    }
 
+    /// A headless engine that renders into an offscreen texture; see
+    /// `WebGPUResources::new_offscreen`.
+    pub async fn offscreen(width: u32, height: u32) -> Result<Self, RendererError> {
+        Self::offscreen_with_config(width, height, &RendererConfig::default()).await
+    }
+
+    /// `offscreen` with the adapter and device picked by `renderer_config`.
+    pub async fn offscreen_with_config(
+        width: u32,
+        height: u32,
+        renderer_config: &RendererConfig,
+    ) -> Result<Self, RendererError> {
+        let webgpu_resource = WebGPUResources::offscreen_with_config(width, height, renderer_config).await?;
+        let mut engine = Self::headless();
+        engine.world.resources.insert_non_send(webgpu_resource);
+        Ok(engine)
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    /// Runs one frame, reading the time from the engine's clock.
+    pub fn update(&mut self) {
+        if let Err(e) = self.world.run_frame() {
+            ::tracing::error!("Failed to run schedule: {}", e);
+        }
+    }
+
+    /// Runs one frame at `timestamp_ms`, e.g. a `requestAnimationFrame`
+    /// timestamp.
+    pub fn update_at(&mut self, timestamp_ms: f64) {
+        if let Err(e) = self.world.run_frame_at(timestamp_ms) {
+            ::tracing::error!("Failed to run schedule: {}", e);
         }
     }
 
-    pub fn test(& mut self) {
-        self.resources.temporal.update();
-        self.world.components.
+    /// Skips the time since the last frame instead of simulating it.
+    pub fn resync_time(&mut self) {
+        if let Some(mut time) = self.world.resources.get_non_send_mut::<AdvancedTime>() {
+            time.resync();
+        }
     }
 }
 
 
+/// Creates the engine on `canvas` and starts its frame loop. `renderer_config`
+/// is a `RendererConfig` as JSON; leave it out for the defaults.
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub async fn initalize_client(canvas: HtmlCanvasElement) {
-    let engine = LuminaEngine::new(canvas).await; 
-    let world = World::new(&engine);
-}
\ No newline at end of file
+pub async fn initalize_client(canvas: HtmlCanvasElement, renderer_config: Option<String>) -> Result<EngineHandle, JsValue> {
+    let renderer_config = match renderer_config {
+        Some(json) => RendererConfig::from_json(&json)
+            .map_err(|e| JsValue::from_str(&format!("invalid renderer config: {}", e)))?,
+        None => RendererConfig::default(),
+    };
+    let engine = LuminaEngine::with_config(canvas, &renderer_config).await?;
+    Ok(EngineLoop::new(engine).start())
+}
@@ -1,4 +1,5 @@
 // input_system.rs
+use crate::components::input_component::InputComponent;
 use crate::engine_core::world::World;
 use crate::ecs_core::system::System;
 
@@ -14,11 +15,17 @@ impl InputSystem {
     }
 }
 
+impl Default for InputSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl System for InputSystem {
-    fn update(&mut self, dt: f32, world: &mut World) {
-        let dt = world.time.scaled_delta();
-        for (entity, input_component) in world.inputs.iter() {
+    fn update(&mut self, world: &mut World) {
+        let mut inputs = world.query::<&InputComponent>();
+        for (_entity, _input_component) in inputs.iter() {
             
         }
     }
-}
\ No newline at end of file
+}
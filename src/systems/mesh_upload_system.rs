@@ -0,0 +1,43 @@
+// mesh_upload_system.rs
+use crate::ecs_core::system::System;
+use crate::engine_core::mesh::{GpuMeshes, Meshes};
+use crate::engine_core::wgpures::{EngineResources, WebGPUResources};
+use crate::engine_core::world::World;
+
+/// Keeps `GpuMeshes` in step with `Meshes`, uploading each mesh once and again
+/// only after it changes. Does nothing without `WebGPUResources`.
+pub struct MeshUploadSystem;
+
+impl MeshUploadSystem {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for MeshUploadSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for MeshUploadSystem {
+    fn update(&mut self, world: &mut World) {
+        if !world.resources.contains_non_send::<WebGPUResources>() {
+            return;
+        }
+        if !world.resources.contains_non_send::<GpuMeshes>() {
+            world.resources.insert_non_send(GpuMeshes::new());
+        }
+        let (Some(meshes), Some(gpu), Some(mut gpu_meshes)) = (
+            world.resource::<Meshes>(),
+            world.resources.get_non_send::<WebGPUResources>(),
+            world.resources.get_non_send_mut::<GpuMeshes>(),
+        ) else {
+            return;
+        };
+        let uploaded = gpu_meshes.sync(gpu.get_device(), gpu.get_queue(), &meshes);
+        if uploaded > 0 {
+            ::tracing::debug!("uploaded {} meshes", uploaded);
+        }
+    }
+}
@@ -1,2 +1,5 @@
 pub mod input_system;
-pub mod rendering_system;
\ No newline at end of file
+pub mod mesh_upload_system;
+pub mod rendering_system;
+pub mod transform_system;
+pub mod timer_system;
\ No newline at end of file
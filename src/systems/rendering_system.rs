@@ -1,6 +1,14 @@
+// rendering_system.rs
+use wgpu::SurfaceError;
 use crate::ecs_core::system::System;
+use crate::engine_core::mesh_pass::MeshPipelines;
+use crate::engine_core::render_graph::{BoundResource, GraphResources};
+use crate::engine_core::rendering::RenderSystem;
+use crate::engine_core::wgpures::{EngineResources, WebGPUResources};
 use crate::engine_core::world::World;
 
+/// Runs the `RenderSystem` graph into the current frame. Does nothing without
+/// `WebGPUResources`, so headless worlds skip it.
 pub struct RenderingSystem;
 
 impl RenderingSystem {
@@ -8,3 +16,75 @@ impl RenderingSystem {
         Self
     }
 }
+
+impl Default for RenderingSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for RenderingSystem {
+    fn update(&mut self, world: &mut World) {
+        if !world.resources.contains_non_send::<WebGPUResources>() {
+            return;
+        }
+        if !world.resources.contains_non_send::<GraphResources>() {
+            world.resources.insert_non_send(GraphResources::new());
+        }
+        if !world.resources.contains_non_send::<MeshPipelines>() {
+            let format = world.resources.get_non_send::<WebGPUResources>().map(|gpu| gpu.get_config().format);
+            if let Some(format) = format {
+                world.resources.insert_non_send(MeshPipelines::new(format));
+            }
+        }
+        let Some(mut rendering) = world.resource_mut::<RenderSystem>() else { return };
+        if rendering.graph().is_empty() {
+            return;
+        }
+        let (Some(mut gpu), Some(mut graph_resources)) = (
+            world.resources.get_non_send_mut::<WebGPUResources>(),
+            world.resources.get_non_send_mut::<GraphResources>(),
+        ) else {
+            return;
+        };
+
+        let frame = match gpu.acquire_frame() {
+            Ok(frame) => frame,
+            Err(SurfaceError::Timeout) => {
+                ::tracing::warn!("timed out acquiring a frame; skipping it");
+                return;
+            }
+            Err(e) => {
+                ::tracing::error!("Failed to acquire frame: {}", e);
+                return;
+            }
+        };
+        let backbuffer = rendering.backbuffer();
+        let imports = [(backbuffer, BoundResource::Texture(frame.view()))];
+        let result = rendering.graph_mut().execute(world, gpu.get_device(), gpu.get_queue(), &mut graph_resources, &imports);
+        if let Err(e) = result {
+            ::tracing::error!("Failed to run render graph: {}", e);
+            frame.discard();
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use crate::engine_core::rendering::RenderSystem;
+    use crate::engine_core::wgpures::WebGPUResources;
+    use crate::LuminaEngine;
+
+    #[test]
+    #[ignore = "needs a GPU adapter; run with --ignored"]
+    fn frame_is_cleared_through_the_graph() {
+        let mut engine = pollster::block_on(LuminaEngine::offscreen(4, 4)).unwrap();
+        let red = wgpu::Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+        engine.world_mut().insert_resource(RenderSystem::with_clear_color(red));
+        engine.update();
+
+        let gpu = engine.world().resources.get_non_send::<WebGPUResources>().unwrap();
+        let image = gpu.read_frame().unwrap();
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,14 @@
+// timer_system.rs
+use crate::ecs_core::system::System;
+use crate::engine_core::temporal::AdvancedTime;
+use crate::engine_core::world::World;
+
+/// Runs the payloads of `AdvancedTime` timers that came due this frame. Does
+/// nothing if the world has no `AdvancedTime`.
+pub struct TimerSystem;
+
+impl System for TimerSystem {
+    fn update(&mut self, world: &mut World) {
+        AdvancedTime::run_due_timers(world);
+    }
+}
@@ -0,0 +1,111 @@
+// transform_system.rs
+use glam::Mat4;
+use crate::components::transform_component::{GlobalTransform, PreviousTransform, Transform};
+use crate::ecs_core::entity::Entity;
+use crate::ecs_core::query::Without;
+use crate::ecs_core::system::{Access, ParallelSystem, System};
+use crate::engine_core::fixed_time::FixedTime;
+use crate::engine_core::scene_graph::{Children, Parent};
+use crate::engine_core::world::World;
+
+/// Label of `TransformSnapshotSystem`. `FixedUpdate` systems that move
+/// interpolated entities should be ordered after it.
+pub const TRANSFORM_SNAPSHOT: &str = "transform_snapshot";
+
+/// Copies `Transform` into `PreviousTransform` at the start of every fixed step.
+pub struct TransformSnapshotSystem;
+
+impl ParallelSystem for TransformSnapshotSystem {
+    fn access(&self) -> Access {
+        Access::new().query::<(&Transform, &mut PreviousTransform)>()
+    }
+
+    fn run(&mut self, world: &World) {
+        for (_, (transform, mut previous)) in world.query::<(&Transform, &mut PreviousTransform)>().iter() {
+            if previous.0 != *transform {
+                previous.0 = *transform;
+            }
+        }
+    }
+}
+
+/// Computes every `GlobalTransform` from the local `Transform`s, walking each
+/// tree from its root so parents are always done before their children.
+/// Entities with a `Transform` but no `GlobalTransform` get one. Entities whose
+/// parent was despawned without `despawn_recursive` are treated as roots, and
+/// ancestors without a `Transform` count as the identity.
+/// Entities with a `PreviousTransform` use the pose blended by the
+/// `FixedTime` interpolation factor.
+pub struct TransformPropagationSystem {
+    roots: Vec<Entity>,
+    pending: Vec<(Entity, Mat4)>,
+}
+
+impl TransformPropagationSystem {
+    pub fn new() -> Self {
+        Self {
+            roots: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Default for TransformPropagationSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl System for TransformPropagationSystem {
+    fn update(&mut self, world: &mut World) {
+        let missing: Vec<Entity> = world
+            .query_filtered::<&Transform, Without<GlobalTransform>>()
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in missing {
+            world.components.insert(entity, GlobalTransform::IDENTITY);
+        }
+
+        let is_root = |parent: Option<&Parent>| parent.is_none_or(|parent| !world.entities.is_alive(parent.get()));
+        self.roots.clear();
+        self.roots.extend(
+            world
+                .query::<(&Transform, Option<&Parent>)>()
+                .iter()
+                .filter(|(_, (_, parent))| is_root(*parent))
+                .map(|(entity, _)| entity),
+        );
+        self.roots.extend(
+            world
+                .query_filtered::<(&Children, Option<&Parent>), Without<Transform>>()
+                .iter()
+                .filter(|(_, (_, parent))| is_root(*parent))
+                .map(|(entity, _)| entity),
+        );
+
+        let alpha = world.resource::<FixedTime>().map_or(1.0, |fixed| fixed.interpolation());
+        let mut transforms = world.query::<(&Transform, Option<&PreviousTransform>)>();
+        let mut children = world.query::<&Children>();
+        let mut globals = world.query::<&mut GlobalTransform>();
+        for &root in &self.roots {
+            self.pending.push((root, Mat4::IDENTITY));
+            while let Some((entity, parent_matrix)) = self.pending.pop() {
+                let matrix = match transforms.get(entity) {
+                    Some((transform, Some(previous))) => parent_matrix * previous.0.lerp(transform, alpha).compute_matrix(),
+                    Some((transform, None)) => parent_matrix * transform.compute_matrix(),
+                    None => parent_matrix,
+                };
+                if let Some(mut global) = globals.get(entity) {
+                    // Only touch it when it moved, so `Changed<GlobalTransform>` means something.
+                    if global.0 != matrix {
+                        global.0 = matrix;
+                    }
+                }
+                if let Some(children) = children.get(entity) {
+                    self.pending.extend(children.as_slice().iter().rev().map(|&child| (child, matrix)));
+                }
+            }
+        }
+    }
+}
@@ -1,5 +1,4 @@
 // tracing.rs
-use tracing_wasm;
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen(start)]
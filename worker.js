@@ -0,0 +1,26 @@
+// worker.js
+// Entry point for the WebWorker pool. Each worker instantiates the same wasm
+// module on the main thread's shared memory, then runs the jobs it is handed
+// by pointer.
+import init, { worker_entry_point, worker_task_failed } from './pkg/luminaengine.js';
+
+let ready = null;
+
+self.onmessage = async ({ data }) => {
+    if (data && data.type === 'init') {
+        ready = init({ module_or_path: data.module, memory: data.memory });
+        await ready;
+        self.postMessage({ type: 'ready' });
+        return;
+    }
+    await ready;
+    const [task, batch] = data;
+    try {
+        worker_entry_point(task, batch);
+    } catch (error) {
+        // A panic traps instead of unwinding; release the job's borrows and
+        // tell the waiting thread so it stops waiting.
+        console.error('web worker job failed:', error);
+        worker_task_failed(batch);
+    }
+};